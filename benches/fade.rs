@@ -0,0 +1,98 @@
+//! Compares the per-pixel fade `fader` started out with against the lookup
+//! table implementation. Run with `cargo bench`.
+
+use fader::{
+    Color, ColorSpace, Desaturate, Dither, Effect, Temperature, apply_effect_into, fade_image_into,
+};
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba, RgbaImage};
+use std::{hint::black_box, time::Instant};
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+const FRAMES: usize = 30;
+
+/// The original implementation: converts the image for every frame and
+/// multiplies each channel through `put_pixel`.
+fn fade_image_per_pixel(img: &DynamicImage, alpha: f32) -> DynamicImage {
+    let (width, height) = img.dimensions();
+    let mut output = ImageBuffer::new(width, height);
+
+    for (x, y, pixel) in img.to_rgba8().enumerate_pixels() {
+        let [r, g, b, a] = pixel.0;
+        let faded_pixel = Rgba([
+            ((r as f32) * alpha) as u8,
+            ((g as f32) * alpha) as u8,
+            ((b as f32) * alpha) as u8,
+            a,
+        ]);
+        output.put_pixel(x, y, faded_pixel);
+    }
+
+    DynamicImage::ImageRgba8(output)
+}
+
+fn bench(name: &str, mut run: impl FnMut(f32)) {
+    let factors: Vec<f32> = (0..FRAMES)
+        .map(|i| 1.0 - i as f32 / (FRAMES - 1) as f32)
+        .collect();
+
+    // Warm up caches and the allocator before measuring.
+    run(0.5);
+
+    let start = Instant::now();
+    for &factor in &factors {
+        run(factor);
+    }
+    let per_frame = start.elapsed() / FRAMES as u32;
+    println!("{name:<16} {per_frame:>12.2?} per {WIDTH}x{HEIGHT} frame");
+}
+
+fn main() {
+    let source = RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        Rgba([(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8, 255])
+    });
+    let dynamic = DynamicImage::ImageRgba8(source.clone());
+
+    bench("per-pixel", |factor| {
+        black_box(fade_image_per_pixel(black_box(&dynamic), factor));
+    });
+
+    let mut frame = RgbaImage::default();
+    for (name, space, dither) in [
+        ("lookup table", ColorSpace::Naive, Dither::None),
+        ("blue noise", ColorSpace::Naive, Dither::BlueNoise),
+        ("linear light", ColorSpace::Linear, Dither::None),
+        ("oklab", ColorSpace::Oklab, Dither::None),
+    ] {
+        bench(name, |factor| {
+            fade_image_into(
+                black_box(&source),
+                factor,
+                Color::BLACK,
+                space,
+                dither,
+                0,
+                &mut frame,
+            );
+            black_box(&frame);
+        });
+    }
+
+    let effects: [(&str, &dyn Effect); 2] = [
+        ("desaturate", &Desaturate),
+        ("temperature", &Temperature::new(3000.0).unwrap()),
+    ];
+    for (name, effect) in effects {
+        bench(name, |factor| {
+            apply_effect_into(
+                black_box(&source),
+                effect,
+                factor,
+                Dither::None,
+                0,
+                &mut frame,
+            );
+            black_box(&frame);
+        });
+    }
+}
@@ -0,0 +1,110 @@
+use std::{fmt, str::FromStr};
+
+/// An opaque sRGB color that fades start from or end at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::new(0, 0, 0);
+    pub const WHITE: Color = Color::new(255, 255, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// The channels as an `[r, g, b]` array.
+    pub const fn channels(self) -> [u8; 3] {
+        [self.r, self.g, self.b]
+    }
+}
+
+const NAMED_COLORS: &[(&str, Color)] = &[
+    ("black", Color::BLACK),
+    ("white", Color::WHITE),
+    ("gray", Color::new(128, 128, 128)),
+    ("grey", Color::new(128, 128, 128)),
+    ("red", Color::new(255, 0, 0)),
+    ("green", Color::new(0, 128, 0)),
+    ("blue", Color::new(0, 0, 255)),
+    ("yellow", Color::new(255, 255, 0)),
+    ("cyan", Color::new(0, 255, 255)),
+    ("magenta", Color::new(255, 0, 255)),
+    ("orange", Color::new(255, 165, 0)),
+    ("purple", Color::new(128, 0, 128)),
+];
+
+impl FromStr for Color {
+    type Err = String;
+
+    /// Parse `#rrggbb`, `#rgb` (the `#` is optional) or a basic color name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some((_, color)) = NAMED_COLORS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+        {
+            return Ok(*color);
+        }
+
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let invalid = || format!("invalid color '{s}', expected #rrggbb, #rgb or a color name");
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let channel = |digits: &str| u8::from_str_radix(digits, 16).map_err(|_| invalid());
+        match hex.len() {
+            6 => Ok(Color::new(
+                channel(&hex[0..2])?,
+                channel(&hex[2..4])?,
+                channel(&hex[4..6])?,
+            )),
+            3 => Ok(Color::new(
+                channel(&hex[0..1])? * 17,
+                channel(&hex[1..2])? * 17,
+                channel(&hex[2..3])? * 17,
+            )),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_and_names() {
+        assert_eq!("#ff8000".parse(), Ok(Color::new(255, 128, 0)));
+        assert_eq!("FF8000".parse(), Ok(Color::new(255, 128, 0)));
+        assert_eq!(" #f80 ".parse(), Ok(Color::new(255, 136, 0)));
+        assert_eq!("Grey".parse(), Ok(Color::new(128, 128, 128)));
+        assert_eq!("BLACK".parse(), Ok(Color::BLACK));
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        for color in [Color::BLACK, Color::WHITE, Color::new(1, 171, 254)] {
+            assert_eq!(color.to_string().parse(), Ok(color));
+        }
+        assert_eq!(Color::new(1, 171, 254).to_string(), "#01abfe");
+    }
+
+    #[test]
+    fn rejects_invalid_colors() {
+        for invalid in [
+            "", "#", "#ff", "#ff80", "#ff80000", "#gg0000", "#+f+f+f", "navy",
+        ] {
+            assert!(invalid.parse::<Color>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use clap::ValueEnum;
+use std::sync::LazyLock;
+
+use crate::dither::to_fixed;
+
+/// Color space in which pixels are interpolated while fading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ColorSpace {
+    /// Interpolate the sRGB-encoded values directly. Fast, but midtones
+    /// darken too early.
+    #[default]
+    Naive,
+    /// Decode to linear light, interpolate and re-encode.
+    Linear,
+    /// Interpolate in OKLab, so lightness changes evenly as perceived.
+    /// Considerably slower than the other spaces.
+    Oklab,
+}
+
+/// Decode an sRGB-encoded value in `0.0..=1.0` to linear light.
+pub(crate) fn decode_srgb(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Linear-light value of every sRGB-encoded byte.
+static SRGB_TO_LINEAR: LazyLock<[f32; 256]> =
+    LazyLock::new(|| std::array::from_fn(|v| decode_srgb(v as f32 / 255.0)));
+
+pub(crate) fn srgb_to_linear(v: u8) -> f32 {
+    SRGB_TO_LINEAR[v as usize]
+}
+
+/// Encode a linear-light value to sRGB, scaled to `0.0..=255.0` and left
+/// unquantized. Interpolates between the bytes that decode just below and
+/// above `c` rather than evaluating the transfer function.
+pub(crate) fn linear_to_srgb(c: f32) -> f32 {
+    let above = SRGB_TO_LINEAR.partition_point(|&linear| linear < c);
+    if above == 0 {
+        return 0.0;
+    }
+    if above == SRGB_TO_LINEAR.len() {
+        return 255.0;
+    }
+    let (low, high) = (SRGB_TO_LINEAR[above - 1], SRGB_TO_LINEAR[above]);
+    (above - 1) as f32 + (c - low) / (high - low)
+}
+
+/// Convert an sRGB-encoded color to OKLab.
+pub(crate) fn srgb_to_oklab(rgb: [u8; 3]) -> [f32; 3] {
+    linear_to_oklab(rgb.map(srgb_to_linear))
+}
+
+/// Convert a linear-light color to OKLab.
+///
+/// The matrices are the ones published with OKLab, kept verbatim.
+#[allow(clippy::excessive_precision)]
+fn linear_to_oklab([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+/// Convert an OKLab color back to sRGB scaled to `0.0..=255.0`, clipping it
+/// to the gamut.
+#[allow(clippy::excessive_precision)]
+pub(crate) fn oklab_to_srgb([lightness, a, b]: [f32; 3]) -> [f32; 3] {
+    let l = (lightness + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m = (lightness - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s = (lightness - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+    [
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ]
+    .map(linear_to_srgb)
+}
+
+impl ColorSpace {
+    /// Table mapping every channel value `v` to the mix of `target` and `v`
+    /// with weight `alpha` on `v` in 8.8 fixed point, for the spaces that
+    /// treat channels independently.
+    pub(crate) fn mix_table(self, target: u8, alpha: f32) -> Option<[u16; 256]> {
+        match self {
+            ColorSpace::Naive => {
+                let target = target as f32;
+                Some(std::array::from_fn(|v| {
+                    to_fixed(target + (v as f32 - target) * alpha)
+                }))
+            }
+            ColorSpace::Linear => {
+                let target = srgb_to_linear(target);
+                Some(std::array::from_fn(|v| {
+                    to_fixed(linear_to_srgb(
+                        target + (srgb_to_linear(v as u8) - target) * alpha,
+                    ))
+                }))
+            }
+            ColorSpace::Oklab => None,
+        }
+    }
+
+    /// Mix two sRGB-encoded colors, where `t` of `0.0` is `from` and `1.0` is
+    /// `to`. The result is scaled to `0.0..=255.0` and left unquantized.
+    pub(crate) fn mix(self, from: [u8; 3], to: [u8; 3], t: f32) -> [f32; 3] {
+        match self {
+            ColorSpace::Naive => std::array::from_fn(|c| {
+                let (a, b) = (from[c] as f32, to[c] as f32);
+                a + (b - a) * t
+            }),
+            ColorSpace::Linear => std::array::from_fn(|c| {
+                let (a, b) = (srgb_to_linear(from[c]), srgb_to_linear(to[c]));
+                linear_to_srgb(a + (b - a) * t)
+            }),
+            ColorSpace::Oklab => mix_oklab(srgb_to_oklab(from), to, t),
+        }
+    }
+
+    /// Mix two unquantized sRGB-encoded colors scaled to `0.0..=255.0`, like
+    /// [`mix`](Self::mix).
+    pub(crate) fn mix_values(self, from: [f32; 3], to: [f32; 3], t: f32) -> [f32; 3] {
+        let decode = |rgb: [f32; 3]| rgb.map(|c| decode_srgb((c / 255.0).clamp(0.0, 1.0)));
+        match self {
+            ColorSpace::Naive => std::array::from_fn(|c| from[c] + (to[c] - from[c]) * t),
+            ColorSpace::Linear => {
+                let (from, to) = (decode(from), decode(to));
+                std::array::from_fn(|c| linear_to_srgb(from[c] + (to[c] - from[c]) * t))
+            }
+            ColorSpace::Oklab => {
+                let (from, to) = (linear_to_oklab(decode(from)), linear_to_oklab(decode(to)));
+                oklab_to_srgb(std::array::from_fn(|c| from[c] + (to[c] - from[c]) * t))
+            }
+        }
+    }
+}
+
+/// Mix a color already converted to OKLab with an sRGB-encoded one, where `t`
+/// of `0.0` is `from` and `1.0` is `to`, like [`ColorSpace::mix`].
+pub(crate) fn mix_oklab(from: [f32; 3], to: [u8; 3], t: f32) -> [f32; 3] {
+    let to = srgb_to_oklab(to);
+    oklab_to_srgb(std::array::from_fn(|c| from[c] + (to[c] - from[c]) * t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPACES: [ColorSpace; 3] = [ColorSpace::Naive, ColorSpace::Linear, ColorSpace::Oklab];
+
+    #[test]
+    fn linear_light_round_trips() {
+        for v in 0..=255u8 {
+            let value = linear_to_srgb(srgb_to_linear(v));
+            assert!((value - v as f32).abs() < 1e-3, "{v}: {value}");
+        }
+        assert_eq!(linear_to_srgb(-0.5), 0.0);
+        assert_eq!(linear_to_srgb(1.5), 255.0);
+    }
+
+    #[test]
+    fn oklab_round_trips() {
+        for rgb in [
+            [0, 0, 0],
+            [255, 255, 255],
+            [255, 0, 0],
+            [12, 200, 99],
+            [1, 2, 254],
+        ] {
+            let value = oklab_to_srgb(srgb_to_oklab(rgb));
+            for (value, v) in value.into_iter().zip(rgb) {
+                assert!((value - v as f32).abs() < 0.05, "{rgb:?}: {value}");
+            }
+        }
+        let [lightness, a, b] = srgb_to_oklab([255, 255, 255]);
+        assert!((lightness - 1.0).abs() < 1e-3 && a.abs() < 1e-3 && b.abs() < 1e-3);
+    }
+
+    #[test]
+    fn mixes_start_and_end_at_their_colors() {
+        let (from, to) = ([200, 30, 90], [10, 250, 120]);
+        for space in SPACES {
+            for (t, rgb) in [(0.0, from), (1.0, to)] {
+                let mixed = space.mix(from, to, t);
+                let values = space.mix_values(from.map(f32::from), to.map(f32::from), t);
+                for ((mixed, value), v) in mixed.into_iter().zip(values).zip(rgb) {
+                    assert!((mixed - v as f32).abs() < 0.05, "{space:?} at {t}: {mixed}");
+                    assert!((value - v as f32).abs() < 0.05, "{space:?} at {t}: {value}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mix_values_matches_mix() {
+        let (from, to) = ([200, 30, 90], [10, 250, 120]);
+        for space in SPACES {
+            let mixed = space.mix(from, to, 0.3);
+            let values = space.mix_values(from.map(f32::from), to.map(f32::from), 0.3);
+            for (mixed, value) in mixed.into_iter().zip(values) {
+                assert!((mixed - value).abs() < 0.05, "{space:?}: {mixed} {value}");
+            }
+        }
+    }
+
+    #[test]
+    fn linear_midpoints_are_brighter() {
+        let naive = ColorSpace::Naive.mix([0; 3], [255; 3], 0.5)[0];
+        let linear = ColorSpace::Linear.mix([0; 3], [255; 3], 0.5)[0];
+        assert_eq!(naive, 127.5);
+        // Half the light of white encodes to about 188 in sRGB.
+        assert!((linear - 187.5).abs() < 0.5, "{linear}");
+    }
+
+    #[test]
+    fn mix_tables_match_mix() {
+        for space in [ColorSpace::Naive, ColorSpace::Linear] {
+            let table = space.mix_table(40, 0.25).unwrap();
+            for v in [0, 40, 128, 255] {
+                let mixed = space.mix([40; 3], [v; 3], 0.25)[0];
+                assert!(
+                    (table[v as usize] as f32 / 256.0 - mixed).abs() < 0.01,
+                    "{space:?}"
+                );
+            }
+        }
+        assert_eq!(ColorSpace::Oklab.mix_table(40, 0.25), None);
+    }
+}
@@ -0,0 +1,285 @@
+use image::{DynamicImage, RgbaImage};
+use std::{borrow::Cow, path::Path};
+
+use crate::{
+    ColorSpace, Dither, Easing, EncoderSettings, FaderError, FrameBuffer, FrameSink, Result,
+    Scaling, blend_images_into, encode::Renderer, error::open_image, render::copy_into, scale,
+    timeline,
+};
+
+/// Builder for a slideshow that dissolves each image into the next.
+///
+/// Every image is shown for the hold time, followed by a transition into the
+/// next image. Without [scaling](Self::scaling), images are resized to the
+/// dimensions of the first one.
+#[derive(Debug, Clone)]
+pub struct Crossfade {
+    images: Vec<RgbaImage>,
+    framerate: u32,
+    transition: f32,
+    hold: f32,
+    easing: Easing,
+    colorspace: ColorSpace,
+    dither: Dither,
+    scaling: Option<Scaling>,
+    encoder: EncoderSettings,
+    jobs: usize,
+}
+
+impl Crossfade {
+    /// Start a crossfade over already decoded images, at 10 fps with 2 second
+    /// transitions and no hold.
+    pub fn new(images: Vec<DynamicImage>) -> Self {
+        Self {
+            images: images.into_iter().map(DynamicImage::into_rgba8).collect(),
+            framerate: 10,
+            transition: 2.0,
+            hold: 0.0,
+            easing: Easing::default(),
+            colorspace: ColorSpace::default(),
+            dither: Dither::default(),
+            scaling: None,
+            encoder: EncoderSettings::default(),
+            jobs: 0,
+        }
+    }
+
+    /// Decode the images at `paths` and start a crossfade over them.
+    pub fn open<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Result<Self> {
+        let images = paths
+            .into_iter()
+            .map(|path| open_image(path.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::new(images))
+    }
+
+    /// Frame rate of the output video.
+    pub fn framerate(mut self, framerate: u32) -> Self {
+        self.framerate = framerate;
+        self
+    }
+
+    /// Duration of each transition in seconds.
+    pub fn transition(mut self, transition: f32) -> Self {
+        self.transition = transition;
+        self
+    }
+
+    /// Time in seconds each image stays on screen between transitions.
+    pub fn hold(mut self, hold: f32) -> Self {
+        self.hold = hold;
+        self
+    }
+
+    /// Easing curve applied to every transition.
+    pub fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Color space in which pixels are interpolated.
+    pub fn colorspace(mut self, colorspace: ColorSpace) -> Self {
+        self.colorspace = colorspace;
+        self
+    }
+
+    /// Sets the [`Dither`] mode.
+    pub fn dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    /// Scale every image to a fixed output size.
+    pub fn scaling(mut self, scaling: Scaling) -> Self {
+        self.scaling = Some(scaling);
+        self
+    }
+
+    /// The images in the order they are shown, before scaling.
+    pub fn images(&self) -> &[RgbaImage] {
+        &self.images
+    }
+
+    /// Render the frames of the slideshow lazily, in order.
+    pub fn frames(&self) -> Result<impl Iterator<Item = RgbaImage> + '_> {
+        Renderer::frames(self)
+    }
+
+    /// Number of frames of the slideshow.
+    pub fn frame_count(&self) -> Result<usize> {
+        Renderer::frame_count(self)
+    }
+
+    /// Options for the encoder used by [`write_video`](Self::write_video).
+    pub fn encoder(mut self, encoder: EncoderSettings) -> Self {
+        self.encoder = encoder;
+        self
+    }
+
+    /// Number of threads rendering frames. `0`, the default, uses every CPU core.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Render every frame and encode them into a video at `output_path`.
+    ///
+    /// The container and codec follow the extension of `output_path`, see
+    /// [`VideoFormat`](crate::VideoFormat).
+    pub fn write_video(&self, output_path: impl AsRef<Path>) -> Result<()> {
+        Renderer::write_video(self, output_path.as_ref())
+    }
+
+    /// Render every frame into `sink` and finalize it.
+    pub fn write_to(&self, sink: &mut dyn FrameSink) -> Result<()> {
+        Renderer::write_to(self, sink)
+    }
+}
+
+impl Renderer for Crossfade {
+    type Step = (usize, f32);
+    /// The images, scaled to the output size.
+    type Scene<'a> = Vec<Cow<'a, RgbaImage>>;
+
+    fn framerate(&self) -> u32 {
+        self.framerate
+    }
+
+    fn encoder_settings(&self) -> &EncoderSettings {
+        &self.encoder
+    }
+
+    fn thread_count(&self) -> usize {
+        self.jobs
+    }
+
+    /// For every frame, the index of the outgoing image and the blend factor
+    /// towards the following one.
+    fn steps(&self) -> Result<Vec<(usize, f32)>> {
+        if self.images.is_empty() {
+            return Err(FaderError::InvalidArgument(
+                "a crossfade needs at least one image".into(),
+            ));
+        }
+        timeline::check_framerate(self.framerate)?;
+        timeline::check_seconds("transition", self.transition)?;
+        timeline::check_seconds("hold", self.hold)?;
+
+        let framerate = self.framerate as f32;
+        let hold_frames = timeline::frames("hold", (self.hold * framerate).round())?.max(1);
+        let transition_frames =
+            timeline::frames("transition", (self.transition * framerate).ceil())?;
+        let count = self.images.len();
+        let total = hold_frames
+            .checked_mul(count)
+            .zip(transition_frames.checked_mul(count - 1))
+            .and_then(|(holds, transitions)| holds.checked_add(transitions));
+        timeline::check_frame_total("crossfade", total)?;
+
+        let mut steps = Vec::new();
+        for index in 0..self.images.len() {
+            steps.extend(std::iter::repeat_n((index, 0.0), hold_frames));
+            if index + 1 < self.images.len() {
+                steps.extend((0..transition_frames).map(|i| {
+                    let t = (i + 1) as f32 / (transition_frames + 1) as f32;
+                    (index, self.easing.apply(t))
+                }));
+            }
+        }
+        Ok(steps)
+    }
+
+    fn scene(&self) -> Self::Scene<'_> {
+        scale::uniform(&self.images, self.scaling.as_ref())
+    }
+
+    /// Render frame `frame_index`, for one step over the scaled `images`, into
+    /// `frame`.
+    fn render(
+        &self,
+        images: &Self::Scene<'_>,
+        frame_index: usize,
+        &(index, t): &(usize, f32),
+        frame: &mut impl FrameBuffer,
+    ) {
+        if t == 0.0 {
+            copy_into(&images[index], frame);
+        } else {
+            blend_images_into(
+                &images[index],
+                &images[index + 1],
+                t,
+                self.colorspace,
+                self.dither,
+                frame_index,
+                frame,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn crossfade(colors: &[[u8; 4]]) -> Crossfade {
+        let images = colors
+            .iter()
+            .map(|&color| DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba(color))))
+            .collect();
+        Crossfade::new(images).framerate(10)
+    }
+
+    #[test]
+    fn holds_each_image_between_transitions() {
+        let job = crossfade(&[[0; 4], [255; 4], [0; 4]])
+            .transition(0.2)
+            .hold(0.3);
+        let (third, two_thirds) = (1.0 / 3.0, 2.0 / 3.0);
+        let expected = [
+            [(0, 0.0); 3].as_slice(),
+            &[(0, third), (0, two_thirds)],
+            &[(1, 0.0); 3],
+            &[(1, third), (1, two_thirds)],
+            &[(2, 0.0); 3],
+        ]
+        .concat();
+        assert_eq!(job.steps().unwrap(), expected);
+        assert_eq!(job.frame_count().unwrap(), 13);
+    }
+
+    #[test]
+    fn shows_every_image_at_least_once() {
+        let job = crossfade(&[[0; 4], [255; 4]]).transition(0.0).hold(0.0);
+        assert_eq!(job.steps().unwrap(), [(0, 0.0), (1, 0.0)]);
+        let single = crossfade(&[[0; 4]]).hold(0.25);
+        assert_eq!(single.steps().unwrap(), [(0, 0.0); 3]);
+    }
+
+    #[test]
+    fn blends_the_frames_of_transitions() {
+        let job = crossfade(&[[0, 0, 0, 255], [200, 100, 50, 255]]).transition(0.1);
+        let frames: Vec<RgbaImage> = job.frames().unwrap().collect();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].get_pixel(1, 1), &Rgba([0, 0, 0, 255]));
+        assert_eq!(frames[1].get_pixel(1, 1), &Rgba([100, 50, 25, 255]));
+        assert_eq!(frames[2].get_pixel(1, 1), &Rgba([200, 100, 50, 255]));
+    }
+
+    #[test]
+    fn rejects_invalid_crossfades() {
+        assert!(Crossfade::new(Vec::new()).steps().is_err());
+        assert!(crossfade(&[[0; 4]]).framerate(0).steps().is_err());
+        assert!(
+            crossfade(&[[0; 4], [0; 4]])
+                .transition(-1.0)
+                .steps()
+                .is_err()
+        );
+        assert!(crossfade(&[[0; 4]]).hold(f32::NAN).steps().is_err());
+        // Two transitions of 2e5 seconds at 30 fps span 12 million frames.
+        let long = crossfade(&[[0; 4], [0; 4], [0; 4]]).framerate(30);
+        assert!(long.transition(2e5).steps().is_err());
+    }
+}
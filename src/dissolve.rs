@@ -0,0 +1,196 @@
+use clap::ValueEnum;
+
+use crate::{
+    matte::Matte,
+    noise::{BLUE_NOISE, BLUE_NOISE_SIZE, Perlin, Rng, white_noise},
+};
+
+/// Noise deciding the order pixels dissolve in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Noise {
+    /// Independent random grains: the classic, sparkly dissolve.
+    #[default]
+    White,
+    /// Grains spread evenly, without the clumps of white noise.
+    Blue,
+    /// Smooth, cloudy blobs growing and merging, `grain` pixels across at
+    /// their largest.
+    Perlin,
+}
+
+/// Number of octaves of Perlin noise, each half the size of the previous.
+const PERLIN_OCTAVES: u32 = 4;
+
+/// A fade that reveals or hides the image grain by grain, in an order given
+/// by noise, instead of changing every pixel at once.
+///
+/// The fade style decides the direction, and the same seed always dissolves
+/// the same pixels first.
+///
+/// ```
+/// use fader::{Dissolve, Noise};
+///
+/// let dissolve = Dissolve::new(Noise::Blue).seed(7).grain(4);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dissolve {
+    noise: Noise,
+    seed: u64,
+    grain: u32,
+    softness: f32,
+}
+
+impl Dissolve {
+    /// Dissolve single pixels in the order of `noise`, with a seed of 0.
+    pub fn new(noise: Noise) -> Self {
+        Self {
+            noise,
+            seed: 0,
+            grain: 1,
+            softness: 0.0,
+        }
+    }
+
+    /// Seed of the noise. Different seeds dissolve the pixels in different orders.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Size in pixels of the grains that dissolve together, at least 1.
+    pub fn grain(mut self, grain: u32) -> Self {
+        self.grain = grain.max(1);
+        self
+    }
+
+    /// Fraction of the fade over which each grain blends, from `0.0` for grains
+    /// that switch abruptly to `1.0`.
+    pub fn softness(mut self, softness: f32) -> Self {
+        self.softness = softness.clamp(0.0, 1.0);
+        self
+    }
+
+    /// The order pixels of a `width` by `height` frame are faded in.
+    pub(crate) fn matte(&self, width: u32, height: u32) -> Matte {
+        let grain = self.grain;
+        let cells = (0..height).flat_map(|y| (0..width).map(move |x| (x, y)));
+        let positions = match self.noise {
+            Noise::White => cells
+                .map(|(x, y)| white_noise(self.seed, x / grain, y / grain))
+                .collect(),
+            Noise::Blue => {
+                // Seeds shift the tiled texture to one of its offsets.
+                let mut rng = Rng::new(self.seed);
+                let n = BLUE_NOISE_SIZE;
+                let (dx, dy) = (rng.below(n), rng.below(n));
+                cells
+                    .map(|(x, y)| {
+                        let cx = (x / grain) as usize + dx;
+                        let cy = (y / grain) as usize + dy;
+                        let rank = BLUE_NOISE[cy % n * n + cx % n];
+                        (rank as f32 + 0.5) / (n * n) as f32
+                    })
+                    .collect()
+            }
+            Noise::Perlin => {
+                let perlin = Perlin::new(self.seed);
+                let values = cells
+                    .map(|(x, y)| {
+                        let (x, y) = (x as f32 + 0.5, y as f32 + 0.5);
+                        (0..PERLIN_OCTAVES)
+                            .map(|octave| {
+                                let scale = (1 << octave) as f32 / grain as f32;
+                                perlin.noise(x * scale, y * scale) / (1 << octave) as f32
+                            })
+                            .sum()
+                    })
+                    .collect();
+                equalize(values)
+            }
+        };
+        Matte::new(width, height, positions, self.softness)
+    }
+}
+
+/// Replace every value by the fraction of values below it, so that the
+/// pixels dissolve at an even pace even where the noise is not uniform.
+/// Equal values keep equal positions.
+fn equalize(values: Vec<f32>) -> Vec<f32> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_unstable_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let count = values.len().max(1) as f32;
+    let mut positions = vec![0.0; values.len()];
+    let mut below = 0;
+    for run in order.chunk_by(|&a, &b| values[a] == values[b]) {
+        let position = (below as f32 + run.len() as f32 / 2.0) / count;
+        for &index in run {
+            positions[index] = position;
+        }
+        below += run.len();
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOISES: [Noise; 3] = [Noise::White, Noise::Blue, Noise::Perlin];
+
+    /// Fraction of the pixels of a 32×32 frame faded at the fade factor `alpha`.
+    fn faded(dissolve: Dissolve, alpha: f32) -> f32 {
+        let matte = dissolve.matte(32, 32);
+        matte.weights(alpha).filter(|&w| w < 0.5).count() as f32 / 1024.0
+    }
+
+    #[test]
+    fn dissolves_at_an_even_pace() {
+        for noise in NOISES {
+            for alpha in [0.25, 0.5, 0.75] {
+                let faded = faded(Dissolve::new(noise).seed(3), alpha);
+                assert!(
+                    (faded - (1.0 - alpha)).abs() < 0.1,
+                    "{noise:?} at {alpha}: {faded}"
+                );
+            }
+            assert_eq!(faded(Dissolve::new(noise), 1.0), 0.0);
+            assert_eq!(faded(Dissolve::new(noise), 0.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn seeds_pick_the_order() {
+        for noise in NOISES {
+            let dissolve = Dissolve::new(noise).grain(3);
+            assert_eq!(dissolve.matte(16, 9), dissolve.matte(16, 9), "{noise:?}");
+            assert_ne!(
+                dissolve.matte(16, 9),
+                dissolve.seed(1).matte(16, 9),
+                "{noise:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn grains_dissolve_together() {
+        for noise in [Noise::White, Noise::Blue] {
+            let weights: Vec<f32> = Dissolve::new(noise)
+                .grain(2)
+                .matte(4, 2)
+                .weights(0.5)
+                .collect();
+            assert_eq!(weights[0], weights[1], "{noise:?}");
+            assert_eq!(weights[0], weights[4], "{noise:?}");
+            assert_eq!(weights[2], weights[7], "{noise:?}");
+        }
+    }
+
+    #[test]
+    fn equalizes_to_ranks() {
+        assert_eq!(
+            equalize(vec![3.0, 1.0, 2.0, 1.0]),
+            [0.875, 0.25, 0.625, 0.25]
+        );
+        assert_eq!(equalize(Vec::new()), Vec::<f32>::new());
+    }
+}
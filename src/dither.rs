@@ -0,0 +1,207 @@
+use clap::ValueEnum;
+use image::Primitive;
+
+use crate::noise::{BLUE_NOISE, BLUE_NOISE_SIZE};
+
+/// How rendered channels are quantized to the 8 or 16 bits of a
+/// [`FrameBuffer`](crate::FrameBuffer).
+///
+/// Slow fades over smooth gradients move each channel by a fraction of a
+/// step per frame, which shows as contours crawling across the image. Adding
+/// a little noise before quantizing trades those contours for fine grain.
+/// Fades, crossfades and keyframe timelines all render with the same modes,
+/// and only round unless told otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Dither {
+    /// Round every channel to the nearest value.
+    #[default]
+    None,
+    /// Ordered dithering with an 8×8 Bayer matrix: a faint regular
+    /// crosshatch that compresses well.
+    Ordered,
+    /// Blue noise: fine grain without visible structure.
+    BlueNoise,
+    /// Blue noise shifted every frame, so the grain averages out over time
+    /// at high frame rates. Costs the most bitrate.
+    Temporal,
+}
+
+/// Offset between the blue noise of consecutive frames, as a rank in the
+/// texture. Close to the golden ratio of the rank count, so that every cell
+/// goes through all thresholds evenly.
+const TEMPORAL_STEP: usize = 2531;
+
+/// Dither thresholds, in 256ths of a channel step, tiled over an image.
+pub(crate) struct Thresholds([u8; BLUE_NOISE_SIZE * BLUE_NOISE_SIZE]);
+
+impl Dither {
+    /// The thresholds used for frame `index` of a video.
+    pub(crate) fn thresholds(self, index: usize) -> Thresholds {
+        let n = BLUE_NOISE_SIZE;
+        Thresholds(match self {
+            Dither::None => [128; BLUE_NOISE_SIZE * BLUE_NOISE_SIZE],
+            Dither::Ordered => std::array::from_fn(|i| bayer(i % n, i / n) * 4 + 2),
+            Dither::BlueNoise => std::array::from_fn(|i| (BLUE_NOISE[i] >> 4) as u8),
+            Dither::Temporal => std::array::from_fn(|i| {
+                let rank = (BLUE_NOISE[i] as usize + index * TEMPORAL_STEP) % (n * n);
+                (rank >> 4) as u8
+            }),
+        })
+    }
+}
+
+impl Thresholds {
+    /// Thresholds of row `y`, repeating every `len()` pixels.
+    pub(crate) fn row(&self, y: usize) -> &[u8] {
+        let n = BLUE_NOISE_SIZE;
+        let start = y % n * n;
+        &self.0[start..start + n]
+    }
+}
+
+/// Entry of the 8×8 Bayer matrix at `x`, `y`, from 0 to 63.
+fn bayer(x: usize, y: usize) -> u8 {
+    const BASE: [[u8; 2]; 2] = [[0, 2], [3, 1]];
+    (0..3).fold(0, |value, bit| {
+        let weight = 1 << (2 * (2 - bit));
+        value + BASE[(y >> bit) & 1][(x >> bit) & 1] * weight
+    })
+}
+
+/// Quantization of rendered values to the channel type of a frame, `u8` or
+/// `u16`. Public in name only, as part of [`FrameBuffer`](crate::FrameBuffer).
+pub trait Quantize: Primitive + Send + Sync {
+    /// Quantize `value`, a channel scaled to `0.0..=255.0`, adding `threshold`
+    /// in 256ths of a step of the type before truncating. A threshold of 128
+    /// rounds to the nearest value.
+    fn quantize(value: f32, threshold: u8) -> Self;
+
+    /// Quantize `value`, a channel in 8.8 fixed point, like [`quantize`](Self::quantize).
+    fn quantize_fixed(value: u16, threshold: u8) -> Self;
+
+    /// Widen an 8-bit channel without changing its value.
+    fn widen(value: u8) -> Self;
+}
+
+impl Quantize for u8 {
+    fn quantize(value: f32, threshold: u8) -> u8 {
+        (value + threshold as f32 / 256.0).clamp(0.0, 255.0) as u8
+    }
+
+    fn quantize_fixed(value: u16, threshold: u8) -> u8 {
+        ((value as u32 + threshold as u32) >> 8).min(255) as u8
+    }
+
+    fn widen(value: u8) -> u8 {
+        value
+    }
+}
+
+/// 16-bit steps per 8-bit step, mapping 255 to 65535.
+const WIDEN: u32 = 257;
+
+impl Quantize for u16 {
+    fn quantize(value: f32, threshold: u8) -> u16 {
+        (value * WIDEN as f32 + threshold as f32 / 256.0).clamp(0.0, 65535.0) as u16
+    }
+
+    fn quantize_fixed(value: u16, threshold: u8) -> u16 {
+        // Scaling the 8.8 value leaves 8 fractional bits of a 16-bit step.
+        ((value as u32 * WIDEN + threshold as u32) >> 8).min(65535) as u16
+    }
+
+    fn widen(value: u8) -> u16 {
+        value as u16 * WIDEN as u16
+    }
+}
+
+/// Convert a channel scaled to `0.0..=255.0` to 8.8 fixed point, rounding
+/// down so that [`Quantize::quantize_fixed`] rounds 8-bit channels exactly
+/// like [`Quantize::quantize`].
+pub(crate) fn to_fixed(value: f32) -> u16 {
+    (value * 256.0).clamp(0.0, 65280.0) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_point_quantizes_like_floats() {
+        for dither in [Dither::None, Dither::Ordered, Dither::BlueNoise] {
+            let thresholds = dither.thresholds(0);
+            for (i, &threshold) in thresholds.row(3).iter().enumerate() {
+                for value in [
+                    0.0,
+                    0.3,
+                    17.5,
+                    17.996,
+                    127.25 + i as f32 / 7.0,
+                    254.9,
+                    255.0,
+                ] {
+                    let fixed = to_fixed(value);
+                    assert_eq!(
+                        u8::quantize_fixed(fixed, threshold),
+                        u8::quantize(value, threshold),
+                        "{value} at {threshold}"
+                    );
+                    let (wide, wide_fixed) = (
+                        u16::quantize(value, threshold),
+                        u16::quantize_fixed(fixed, threshold),
+                    );
+                    assert!(
+                        wide.abs_diff(wide_fixed) <= 1,
+                        "{value}: {wide} {wide_fixed}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rounds_to_the_nearest_value_without_dither() {
+        assert_eq!(u8::quantize(17.49, 128), 17);
+        assert_eq!(u8::quantize(17.5, 128), 18);
+        assert_eq!(u16::quantize(17.5, 128), 4498);
+        assert_eq!(u8::quantize(-3.0, 128), 0);
+        assert_eq!(u8::quantize(300.0, 128), 255);
+        assert_eq!(u16::quantize(300.0, 128), 65535);
+        assert_eq!(u16::quantize_fixed(to_fixed(255.0), 255), 65535);
+    }
+
+    #[test]
+    fn widening_keeps_the_range() {
+        assert_eq!(u8::widen(200), 200);
+        assert_eq!(u16::widen(0), 0);
+        assert_eq!(u16::widen(255), 65535);
+        assert_eq!(u16::quantize(200.0, 128), u16::widen(200));
+    }
+
+    #[test]
+    fn thresholds_average_to_a_half() {
+        for dither in [Dither::Ordered, Dither::BlueNoise, Dither::Temporal] {
+            let thresholds = dither.thresholds(5);
+            let n = BLUE_NOISE_SIZE;
+            let sum: usize = (0..n)
+                .flat_map(|y| thresholds.row(y).iter())
+                .map(|&t| t as usize)
+                .sum();
+            let mean = sum as f32 / (n * n) as f32;
+            assert!((mean - 128.0).abs() < 1.0, "{dither:?}: {mean}");
+        }
+    }
+
+    #[test]
+    fn temporal_dither_changes_every_frame() {
+        let (first, second) = (
+            Dither::Temporal.thresholds(0),
+            Dither::Temporal.thresholds(1),
+        );
+        assert_ne!(first.row(0), second.row(0));
+        assert_eq!(
+            Dither::BlueNoise.thresholds(0).row(0),
+            Dither::BlueNoise.thresholds(1).row(0)
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use std::{f32::consts::PI, fmt, str::FromStr};
+
+/// Maps normalized time in `[0, 1]` to normalized progress in `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Easing {
+    /// Constant speed.
+    #[default]
+    Linear,
+    /// CSS `ease-in`: starts slowly, ends abruptly.
+    EaseIn,
+    /// CSS `ease-out`: starts abruptly, ends slowly.
+    EaseOut,
+    /// CSS `ease-in-out`: slow at both ends.
+    EaseInOut,
+    /// Cubic ease-in-out.
+    Cubic,
+    /// Sinusoidal ease-in-out.
+    Sine,
+    /// Exponential ease-in-out.
+    Exponential,
+    /// Hermite smoothstep, `3t² - 2t³`.
+    Smoothstep,
+    /// CSS-style cubic Bézier through `(0, 0)`, `(x1, y1)`, `(x2, y2)`, `(1, 1)`.
+    CubicBezier(f32, f32, f32, f32),
+}
+
+impl Easing {
+    /// Progress at normalized time `t`, clamped to `[0, 1]`.
+    ///
+    /// The progress is clamped to `[0, 1]` as well, so Bézier curves that
+    /// overshoot hold at the ends rather than fading past them.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        let progress = match self {
+            Easing::Linear => t,
+            Easing::EaseIn => cubic_bezier(0.42, 0.0, 1.0, 1.0, t),
+            Easing::EaseOut => cubic_bezier(0.0, 0.0, 0.58, 1.0, t),
+            Easing::EaseInOut => cubic_bezier(0.42, 0.0, 0.58, 1.0, t),
+            Easing::Cubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::Sine => (1.0 - (PI * t).cos()) / 2.0,
+            Easing::Exponential => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else if t < 0.5 {
+                    2f32.powf(20.0 * t - 10.0) / 2.0
+                } else {
+                    (2.0 - 2f32.powf(-20.0 * t + 10.0)) / 2.0
+                }
+            }
+            Easing::Smoothstep => t * t * (3.0 - 2.0 * t),
+            Easing::CubicBezier(x1, y1, x2, y2) => cubic_bezier(x1, y1, x2, y2, t),
+        };
+        progress.clamp(0.0, 1.0)
+    }
+}
+
+/// Evaluate the Bézier curve's `y` at the parameter whose `x` equals `x`.
+fn cubic_bezier(x1: f32, y1: f32, x2: f32, y2: f32, x: f32) -> f32 {
+    let bezier = |p1: f32, p2: f32, s: f32| {
+        let inv = 1.0 - s;
+        3.0 * inv * inv * s * p1 + 3.0 * inv * s * s * p2 + s * s * s
+    };
+    let slope = |p1: f32, p2: f32, s: f32| {
+        let inv = 1.0 - s;
+        3.0 * inv * inv * p1 + 6.0 * inv * s * (p2 - p1) + 3.0 * s * s * (1.0 - p2)
+    };
+
+    // Newton's method converges quickly on well-behaved curves; fall back to
+    // bisection when the slope flattens out.
+    let mut s = x;
+    for _ in 0..8 {
+        let error = bezier(x1, x2, s) - x;
+        if error.abs() < 1e-6 {
+            return bezier(y1, y2, s);
+        }
+        let d = slope(x1, x2, s);
+        if d.abs() < 1e-6 {
+            break;
+        }
+        s = (s - error / d).clamp(0.0, 1.0);
+    }
+
+    let (mut lo, mut hi) = (0.0, 1.0);
+    s = x;
+    for _ in 0..32 {
+        let value = bezier(x1, x2, s);
+        if (value - x).abs() < 1e-6 {
+            break;
+        }
+        if value < x {
+            lo = s;
+        } else {
+            hi = s;
+        }
+        s = (lo + hi) / 2.0;
+    }
+    bezier(y1, y2, s)
+}
+
+impl FromStr for Easing {
+    type Err = String;
+
+    /// Parse a curve name, or `cubic-bezier(x1,y1,x2,y2)` for custom control points.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let easing = match s {
+            "linear" => Easing::Linear,
+            "ease-in" => Easing::EaseIn,
+            "ease-out" => Easing::EaseOut,
+            "ease-in-out" => Easing::EaseInOut,
+            "cubic" => Easing::Cubic,
+            "sine" => Easing::Sine,
+            "exponential" => Easing::Exponential,
+            "smoothstep" => Easing::Smoothstep,
+            _ => {
+                let points = s
+                    .strip_prefix("cubic-bezier(")
+                    .and_then(|rest| rest.strip_suffix(')'))
+                    .ok_or_else(|| {
+                        format!(
+                            "unknown easing '{s}', expected one of linear, ease-in, ease-out, \
+                             ease-in-out, cubic, sine, exponential, smoothstep or \
+                             cubic-bezier(x1,y1,x2,y2)"
+                        )
+                    })?;
+                let values = points
+                    .split(',')
+                    .map(|v| v.trim().parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| format!("invalid cubic-bezier control point: {e}"))?;
+                let [x1, y1, x2, y2] = values[..] else {
+                    return Err("cubic-bezier expects exactly four control point values".into());
+                };
+                if !values.iter().all(|v| v.is_finite()) {
+                    return Err("cubic-bezier control points must be finite numbers".into());
+                }
+                if !(0.0..=1.0).contains(&x1) || !(0.0..=1.0).contains(&x2) {
+                    return Err("cubic-bezier x coordinates must lie within [0, 1]".into());
+                }
+                Easing::CubicBezier(x1, y1, x2, y2)
+            }
+        };
+        Ok(easing)
+    }
+}
+
+impl fmt::Display for Easing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Easing::Linear => f.write_str("linear"),
+            Easing::EaseIn => f.write_str("ease-in"),
+            Easing::EaseOut => f.write_str("ease-out"),
+            Easing::EaseInOut => f.write_str("ease-in-out"),
+            Easing::Cubic => f.write_str("cubic"),
+            Easing::Sine => f.write_str("sine"),
+            Easing::Exponential => f.write_str("exponential"),
+            Easing::Smoothstep => f.write_str("smoothstep"),
+            Easing::CubicBezier(x1, y1, x2, y2) => {
+                write!(f, "cubic-bezier({x1},{y1},{x2},{y2})")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMED: [Easing; 8] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+        Easing::Cubic,
+        Easing::Sine,
+        Easing::Exponential,
+        Easing::Smoothstep,
+    ];
+
+    #[test]
+    fn curves_start_at_zero_and_end_at_one() {
+        for easing in NAMED
+            .into_iter()
+            .chain([Easing::CubicBezier(0.1, 0.7, 0.9, 0.2)])
+        {
+            assert!(easing.apply(0.0).abs() < 1e-5, "{easing}");
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-5, "{easing}");
+            assert_eq!(easing.apply(-1.0), easing.apply(0.0), "{easing}");
+            assert_eq!(easing.apply(2.0), easing.apply(1.0), "{easing}");
+        }
+    }
+
+    #[test]
+    fn symmetric_curves_pass_through_the_middle() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseInOut,
+            Easing::Cubic,
+            Easing::Sine,
+            Easing::Exponential,
+            Easing::Smoothstep,
+        ] {
+            assert!((easing.apply(0.5) - 0.5).abs() < 1e-4, "{easing}");
+        }
+    }
+
+    #[test]
+    fn cubic_bezier_matches_css_keywords() {
+        // CSS defines ease-in-out as cubic-bezier(0.42, 0, 0.58, 1).
+        let bezier = Easing::CubicBezier(0.42, 0.0, 0.58, 1.0);
+        for i in 0..=20 {
+            let t = i as f32 / 20.0;
+            assert!((bezier.apply(t) - Easing::EaseInOut.apply(t)).abs() < 1e-6);
+        }
+        // A diagonal curve is linear.
+        let linear = Easing::CubicBezier(0.25, 0.25, 0.75, 0.75);
+        for i in 0..=20 {
+            let t = i as f32 / 20.0;
+            assert!((linear.apply(t) - t).abs() < 1e-4, "{t}");
+        }
+        // Known value of CSS `ease-in` halfway through.
+        assert!((Easing::EaseIn.apply(0.5) - 0.3153).abs() < 1e-3);
+    }
+
+    #[test]
+    fn overshooting_curves_are_clamped() {
+        let easing = Easing::CubicBezier(0.3, -0.5, 0.7, 1.5);
+        for i in 0..=100 {
+            let progress = easing.apply(i as f32 / 100.0);
+            assert!((0.0..=1.0).contains(&progress), "{progress}");
+        }
+    }
+
+    #[test]
+    fn parses_names_and_round_trips() {
+        for easing in NAMED {
+            assert_eq!(easing.to_string().parse::<Easing>(), Ok(easing));
+        }
+        assert_eq!(
+            " cubic-bezier( 0.1, -2, 0.9 ,3 ) ".parse(),
+            Ok(Easing::CubicBezier(0.1, -2.0, 0.9, 3.0))
+        );
+        let bezier = Easing::CubicBezier(0.25, 0.1, 0.25, 1.0);
+        assert_eq!(bezier.to_string().parse(), Ok(bezier));
+    }
+
+    #[test]
+    fn rejects_invalid_curves() {
+        for invalid in [
+            "bounce",
+            "cubic-bezier(0.1,0.2,0.3)",
+            "cubic-bezier(0.1,0.2,0.3,0.4,0.5)",
+            "cubic-bezier(0.1,x,0.3,0.4)",
+            "cubic-bezier(0.5,nan,0.5,1)",
+            "cubic-bezier(0.5,0,0.5,inf)",
+            "cubic-bezier(0.5,-inf,0.5,1)",
+            "cubic-bezier(nan,0,0.5,1)",
+            "cubic-bezier(-0.1,0,0.5,1)",
+            "cubic-bezier(0.5,0,1.1,1)",
+            "cubic-bezier(0.1,0.2,0.3,0.4",
+        ] {
+            assert!(invalid.parse::<Easing>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use std::fmt;
+
+use crate::{
+    Color, ColorSpace, FaderError, Result,
+    colorspace::{decode_srgb, linear_to_srgb, mix_oklab, srgb_to_linear, srgb_to_oklab},
+};
+
+/// A change to the colors of an image that a fade animates, such as darkening
+/// it to a color or draining its saturation.
+///
+/// The fade factor of each frame decides how much of the effect shows: `1.0`
+/// leaves the image unchanged and `0.0` applies the effect fully. Effects only
+/// change colors, the alpha channel is kept as is.
+///
+/// ```no_run
+/// use fader::{Effect, FadeJob};
+///
+/// /// Fade to the negative of the image.
+/// #[derive(Debug)]
+/// struct Invert;
+///
+/// impl Effect for Invert {
+///     fn apply(&self, rgb: [u8; 3], factor: f32) -> [f32; 3] {
+///         rgb.map(|c| {
+///             let inverted = 255.0 - c as f32;
+///             inverted + (c as f32 - inverted) * factor
+///         })
+///     }
+/// }
+///
+/// let job = FadeJob::open("background.png")?.effect(Invert);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub trait Effect: fmt::Debug + Send + Sync {
+    /// The sRGB-encoded pixel `rgb` with the effect applied at `factor`,
+    /// scaled to `0.0..=255.0`. The result is left unquantized so that frames
+    /// can be dithered, and values out of range are clipped.
+    fn apply(&self, rgb: [u8; 3], factor: f32) -> [f32; 3];
+}
+
+/// Run `grade` on a pixel decoded to linear light and encode the result again.
+fn in_linear(rgb: [u8; 3], grade: impl FnOnce([f32; 3]) -> [f32; 3]) -> [f32; 3] {
+    grade(rgb.map(srgb_to_linear)).map(linear_to_srgb)
+}
+
+/// Relative luminance of a linear-light color, with the Rec. 709 primaries
+/// that sRGB shares.
+fn luminance([r, g, b]: [f32; 3]) -> f32 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// The fade of [`fade_image`](crate::fade_image): blend towards a solid color
+/// in a color space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorFade {
+    color: Color,
+    space: ColorSpace,
+    oklab: [f32; 3],
+}
+
+impl ColorFade {
+    /// Fade to `color`, interpolating in `space`.
+    pub fn new(color: Color, space: ColorSpace) -> Self {
+        Self {
+            color,
+            space,
+            // Only OKLab mixes channels, convert the color just once.
+            oklab: srgb_to_oklab(color.channels()),
+        }
+    }
+}
+
+impl Effect for ColorFade {
+    fn apply(&self, rgb: [u8; 3], factor: f32) -> [f32; 3] {
+        match self.space {
+            ColorSpace::Oklab => mix_oklab(self.oklab, rgb, factor),
+            space => space.mix(self.color.channels(), rgb, factor),
+        }
+    }
+}
+
+/// Drain the saturation of the image down to grayscale of the same luminance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Desaturate;
+
+impl Effect for Desaturate {
+    fn apply(&self, rgb: [u8; 3], factor: f32) -> [f32; 3] {
+        in_linear(rgb, |linear| {
+            let gray = luminance(linear);
+            linear.map(|c| lerp(gray, c, factor))
+        })
+    }
+}
+
+/// Tone the image like an old photograph, with the classic sepia matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sepia;
+
+impl Effect for Sepia {
+    fn apply(&self, rgb: [u8; 3], factor: f32) -> [f32; 3] {
+        let [r, g, b] = rgb.map(f32::from);
+        let toned = [
+            0.393 * r + 0.769 * g + 0.189 * b,
+            0.349 * r + 0.686 * g + 0.168 * b,
+            0.272 * r + 0.534 * g + 0.131 * b,
+        ]
+        .map(|c| decode_srgb((c / 255.0).min(1.0)));
+        in_linear(rgb, |linear| {
+            std::array::from_fn(|c| lerp(toned[c], linear[c], factor))
+        })
+    }
+}
+
+/// Lower the contrast until the whole image is a flat middle gray.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Contrast;
+
+/// Linear light of the gray that [`Contrast`] flattens images to, halfway
+/// between black and white as perceived.
+const MIDDLE_GRAY: f32 = 0.18;
+
+impl Effect for Contrast {
+    fn apply(&self, rgb: [u8; 3], factor: f32) -> [f32; 3] {
+        // Contrast is perceived on the encoded values, so scale those.
+        let gray = linear_to_srgb(MIDDLE_GRAY);
+        rgb.map(|c| lerp(gray, c as f32, factor))
+    }
+}
+
+/// Change the exposure of the image by a number of photographic stops,
+/// each doubling or halving the light.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Exposure {
+    stops: f32,
+}
+
+impl Exposure {
+    /// Reach an exposure `stops` away from the image's, negative to darken it
+    /// and positive to brighten it until the highlights clip. Fails unless
+    /// `stops` lies within `-20.0..=20.0`, past which every pixel is black or
+    /// white.
+    pub fn new(stops: f32) -> Result<Self> {
+        if !(-MAX_STOPS..=MAX_STOPS).contains(&stops) {
+            return Err(FaderError::InvalidArgument(format!(
+                "exposure must be between -{MAX_STOPS} and {MAX_STOPS} stops, got {stops}"
+            )));
+        }
+        Ok(Self { stops })
+    }
+}
+
+/// Largest change of exposure in either direction, in stops.
+const MAX_STOPS: f32 = 20.0;
+
+impl Effect for Exposure {
+    fn apply(&self, rgb: [u8; 3], factor: f32) -> [f32; 3] {
+        let gain = (self.stops * (1.0 - factor)).exp2();
+        in_linear(rgb, |linear| linear.map(|c| c * gain))
+    }
+}
+
+/// Shift the white balance of the image to another color temperature, such as
+/// the warm light of dusk after daylight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Temperature {
+    gains: [f32; 3],
+}
+
+/// Color temperature of daylight, which images are assumed to be lit by.
+const DAYLIGHT_KELVIN: f32 = 6500.0;
+
+impl Temperature {
+    /// Reach the light of `kelvin`, below 6500 K for warmer light and above
+    /// for colder. Temperatures are clamped to `1000.0..=40000.0`, and
+    /// infinite or NaN ones fail.
+    pub fn new(kelvin: f32) -> Result<Self> {
+        if !kelvin.is_finite() {
+            return Err(FaderError::InvalidArgument(format!(
+                "color temperature must be a finite number of kelvin, got {kelvin}"
+            )));
+        }
+        let target = blackbody(kelvin.clamp(1000.0, 40000.0));
+        let daylight = blackbody(DAYLIGHT_KELVIN);
+        let gains: [f32; 3] = std::array::from_fn(|c| target[c] / daylight[c]);
+        // Keep the brightness of the image, only its tint changes.
+        let scale = luminance(gains);
+        Ok(Self {
+            gains: gains.map(|gain| gain / scale),
+        })
+    }
+}
+
+impl Effect for Temperature {
+    fn apply(&self, rgb: [u8; 3], factor: f32) -> [f32; 3] {
+        in_linear(rgb, |linear| {
+            std::array::from_fn(|c| linear[c] * lerp(self.gains[c], 1.0, factor))
+        })
+    }
+}
+
+/// Linear-light color of a black body at `kelvin`, after Tanner Helland's fit
+/// of the CIE color matching functions.
+#[allow(clippy::excessive_precision)]
+fn blackbody(kelvin: f32) -> [f32; 3] {
+    let t = kelvin / 100.0;
+    let (r, g) = if t <= 66.0 {
+        (255.0, 99.4708025861 * t.ln() - 161.1195681661)
+    } else {
+        (
+            329.698727446 * (t - 60.0).powf(-0.1332047592),
+            288.1221695283 * (t - 60.0).powf(-0.0755148492),
+        )
+    };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.5177312231 * (t - 10.0).ln() - 305.0447927307
+    };
+    // Keep a trace of every channel so the gains stay finite.
+    [r, g, b].map(|c: f32| decode_srgb((c / 255.0).clamp(1.0 / 255.0, 1.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIXELS: [[u8; 3]; 5] = [
+        [0, 0, 0],
+        [255, 255, 255],
+        [200, 30, 90],
+        [12, 128, 250],
+        [1, 2, 3],
+    ];
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3], what: &str) {
+        for (actual, expected) in actual.into_iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 0.1,
+                "{what}: {actual} != {expected}"
+            );
+        }
+    }
+
+    fn effects() -> Vec<Box<dyn Effect>> {
+        vec![
+            Box::new(ColorFade::new(Color::new(255, 128, 0), ColorSpace::Naive)),
+            Box::new(ColorFade::new(Color::new(255, 128, 0), ColorSpace::Linear)),
+            Box::new(ColorFade::new(Color::new(255, 128, 0), ColorSpace::Oklab)),
+            Box::new(Desaturate),
+            Box::new(Sepia),
+            Box::new(Contrast),
+            Box::new(Exposure::new(-3.0).unwrap()),
+            Box::new(Exposure::new(2.0).unwrap()),
+            Box::new(Temperature::new(2500.0).unwrap()),
+            Box::new(Temperature::new(12000.0).unwrap()),
+        ]
+    }
+
+    #[test]
+    fn effects_keep_the_image_at_factor_one() {
+        for effect in effects() {
+            for rgb in PIXELS {
+                assert_close(
+                    effect.apply(rgb, 1.0),
+                    rgb.map(f32::from),
+                    &format!("{effect:?}"),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn effects_reach_their_target_at_factor_zero() {
+        for space in [ColorSpace::Naive, ColorSpace::Linear, ColorSpace::Oklab] {
+            let fade = ColorFade::new(Color::new(255, 128, 0), space);
+            assert_close(
+                fade.apply([12, 128, 250], 0.0),
+                [255.0, 128.0, 0.0],
+                &format!("{space:?}"),
+            );
+        }
+
+        let [r, g, b] = Desaturate.apply([200, 30, 90], 0.0);
+        assert!((r - g).abs() < 0.1 && (g - b).abs() < 0.1, "{r} {g} {b}");
+
+        let gray = linear_to_srgb(MIDDLE_GRAY);
+        for rgb in PIXELS {
+            assert_close(Contrast.apply(rgb, 0.0), [gray; 3], "contrast");
+        }
+
+        // One stop down halves the light of white.
+        let half = linear_to_srgb(0.5);
+        assert_close(
+            Exposure::new(-1.0).unwrap().apply([255; 3], 0.0),
+            [half; 3],
+            "exposure",
+        );
+        assert_close(
+            Exposure::new(20.0).unwrap().apply([1; 3], 0.0),
+            [255.0; 3],
+            "exposure",
+        );
+        let halfway = Exposure::new(-2.0).unwrap().apply([255; 3], 0.5);
+        assert_close(halfway, [half; 3], "exposure");
+
+        let [r, _, b] = Sepia.apply([128; 3], 0.0);
+        assert!(r > b, "sepia: {r} {b}");
+        let [r, _, b] = Temperature::new(2500.0).unwrap().apply([128; 3], 0.0);
+        assert!(r > b, "warm: {r} {b}");
+        let [r, _, b] = Temperature::new(12000.0).unwrap().apply([128; 3], 0.0);
+        assert!(r < b, "cold: {r} {b}");
+        let daylight = Temperature::new(DAYLIGHT_KELVIN).unwrap();
+        assert_close(
+            daylight.apply([200, 30, 90], 0.0),
+            [200.0, 30.0, 90.0],
+            "daylight",
+        );
+    }
+
+    #[test]
+    fn validates_stops_and_temperatures() {
+        for stops in [-20.0, -3.0, 0.0, 20.0] {
+            assert!(Exposure::new(stops).is_ok(), "{stops}");
+        }
+        for stops in [-20.5, 21.0, f32::NAN, f32::INFINITY] {
+            assert!(Exposure::new(stops).is_err(), "{stops}");
+        }
+        for kelvin in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            assert!(Temperature::new(kelvin).is_err(), "{kelvin}");
+        }
+        assert_eq!(
+            Temperature::new(10.0).unwrap(),
+            Temperature::new(1000.0).unwrap()
+        );
+    }
+}
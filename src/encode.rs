@@ -0,0 +1,461 @@
+use clap::ValueEnum;
+use image::{ImageBuffer, Rgba, RgbaImage, buffer::ConvertBuffer};
+use rayon::{ThreadPoolBuilder, prelude::*};
+use std::path::Path;
+
+use crate::{FaderError, FrameBuffer, Result};
+
+#[cfg(feature = "animation")]
+mod animation;
+#[cfg(feature = "av1")]
+mod av1;
+#[cfg(feature = "ffmpeg")]
+mod ffmpeg;
+mod sequence;
+
+#[cfg(feature = "animation")]
+pub use animation::{ApngSink, GifSink};
+#[cfg(feature = "av1")]
+pub use av1::Av1Sink;
+#[cfg(feature = "ffmpeg")]
+pub use ffmpeg::FfmpegSink;
+pub use sequence::{FrameFormat, FramePattern, ImageSequenceSink};
+
+/// An RGBA image with 16 bits per channel.
+pub type Rgba16Image = ImageBuffer<Rgba<u16>, Vec<u16>>;
+
+/// Number of bits per channel of frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitDepth {
+    /// 8 bits, as in [`RgbaImage`].
+    Eight,
+    /// 16 bits, as in [`Rgba16Image`].
+    Sixteen,
+}
+
+/// Destination for rendered frames, such as a video encoder.
+///
+/// Frames are written in presentation order and all have the same dimensions.
+pub trait FrameSink {
+    /// Append one frame to the output.
+    fn write_frame(&mut self, frame: &RgbaImage) -> Result<()>;
+
+    /// Append one frame with 16 bits per channel to the output. By default
+    /// the frame is rounded to 8 bits and passed to
+    /// [`write_frame`](Self::write_frame).
+    fn write_frame16(&mut self, frame: &Rgba16Image) -> Result<()> {
+        self.write_frame(&frame.convert())
+    }
+
+    /// Whether the sink takes frames with `depth` bits per channel, by
+    /// default only 8. Jobs render every frame at each depth their sink
+    /// takes, passing 16-bit frames to [`write_frame16`](Self::write_frame16).
+    fn takes(&self, depth: BitDepth) -> bool {
+        depth == BitDepth::Eight
+    }
+
+    /// Flush any buffered frames and finalize the output.
+    fn finish(&mut self) -> Result<()>;
+}
+
+/// Writes every frame to each of the sinks in turn, such as a video and an
+/// [image sequence](ImageSequenceSink) of the same frames. Each sink is given
+/// the frames of the depth it takes.
+impl FrameSink for Vec<Box<dyn FrameSink>> {
+    fn write_frame(&mut self, frame: &RgbaImage) -> Result<()> {
+        self.iter_mut()
+            .filter(|sink| sink.takes(BitDepth::Eight))
+            .try_for_each(|sink| sink.write_frame(frame))
+    }
+
+    fn write_frame16(&mut self, frame: &Rgba16Image) -> Result<()> {
+        self.iter_mut()
+            .filter(|sink| sink.takes(BitDepth::Sixteen))
+            .try_for_each(|sink| sink.write_frame16(frame))
+    }
+
+    fn takes(&self, depth: BitDepth) -> bool {
+        self.iter().any(|sink| sink.takes(depth))
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.iter_mut().try_for_each(|sink| sink.finish())
+    }
+}
+
+/// How frames are handed to `ffmpeg`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum FfmpegInput {
+    /// Stream raw RGBA frames over stdin as they are rendered.
+    #[default]
+    Pipe,
+    /// Save every frame as a PNG in a temporary directory first. Slower, but
+    /// handy for debugging.
+    Png,
+}
+
+/// Error diffusion applied when reducing GIF frames to their palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum GifDither {
+    /// Map every pixel to its nearest palette color.
+    None,
+    /// Spread the quantization error to neighbouring pixels.
+    #[default]
+    FloydSteinberg,
+}
+
+/// Video codec `ffmpeg` encodes MP4, WebM, QuickTime and IVF output with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum VideoCodec {
+    /// H.264 with libx264, the default for `.mp4`.
+    H264,
+    /// H.265/HEVC with libx265.
+    H265,
+    /// VP9 with libvpx, the default for `.webm`. Keeps the alpha channel.
+    Vp9,
+    /// AV1 with libaom, the default for `.ivf`.
+    Av1,
+    /// ProRes 4444, the default for `.mov`. Keeps the alpha channel.
+    Prores,
+}
+
+impl VideoCodec {
+    /// The codec used for `format` unless another one is chosen, if the format
+    /// is a video container.
+    pub fn default_for(format: VideoFormat) -> Option<Self> {
+        match format {
+            VideoFormat::Mp4 => Some(VideoCodec::H264),
+            VideoFormat::WebM => Some(VideoCodec::Vp9),
+            VideoFormat::ProRes => Some(VideoCodec::Prores),
+            VideoFormat::Ivf => Some(VideoCodec::Av1),
+            VideoFormat::Apng | VideoFormat::Gif | VideoFormat::WebP => None,
+        }
+    }
+
+    /// Whether the container of `format` can hold the codec.
+    pub fn fits(self, format: VideoFormat) -> bool {
+        match format {
+            VideoFormat::Mp4 => !matches!(self, VideoCodec::Prores),
+            VideoFormat::WebM | VideoFormat::Ivf => {
+                matches!(self, VideoCodec::Vp9 | VideoCodec::Av1)
+            }
+            VideoFormat::ProRes => {
+                matches!(
+                    self,
+                    VideoCodec::H264 | VideoCodec::H265 | VideoCodec::Prores
+                )
+            }
+            VideoFormat::Apng | VideoFormat::Gif | VideoFormat::WebP => false,
+        }
+    }
+
+    /// Whether the codec keeps the alpha channel of the frames.
+    pub fn supports_alpha(self) -> bool {
+        matches!(self, VideoCodec::Vp9 | VideoCodec::Prores)
+    }
+}
+
+/// Options for the encoder backends.
+#[derive(Debug, Clone)]
+pub struct EncoderSettings {
+    /// How frames reach `ffmpeg`, when it is the backend.
+    pub ffmpeg_input: FfmpegInput,
+    /// Number of times animated images (GIF, APNG, WebP) play, `0` meaning forever.
+    pub loop_count: u16,
+    /// Size of each GIF frame's palette, between 2 and 256.
+    pub gif_colors: u16,
+    /// Dithering used when quantizing GIF frames.
+    pub gif_dither: GifDither,
+    /// Codec of video output, `None` using the default of the format.
+    pub codec: Option<VideoCodec>,
+    /// Constant rate factor of the codec: lower means better quality and
+    /// bigger files. Up to 51 for H.264 and H.265, 63 for VP9 and AV1.
+    pub crf: Option<u8>,
+    /// Target bitrate in `ffmpeg` notation such as `8M`, instead of a
+    /// constant quality.
+    pub bitrate: Option<String>,
+    /// Speed preset of the encoder: a name such as `slow` for H.264 and H.265,
+    /// or a number from 0 (slowest) to 8 for VP9 and AV1.
+    pub preset: Option<String>,
+    /// `ffmpeg` pixel format of video output, such as `yuv420p10le` for 10 bits
+    /// per channel.
+    pub pixel_format: Option<String>,
+    /// Extra arguments handed to `ffmpeg` just before the output path.
+    pub ffmpeg_args: Vec<String>,
+}
+
+impl EncoderSettings {
+    /// Whether any setting only `ffmpeg` understands is given, in which case
+    /// `ffmpeg` encodes every format.
+    pub fn needs_ffmpeg(&self) -> bool {
+        self.has_codec_options() || !self.ffmpeg_args.is_empty()
+    }
+
+    /// Whether any option of the video codec is given.
+    pub(crate) fn has_codec_options(&self) -> bool {
+        self.codec.is_some()
+            || self.crf.is_some()
+            || self.bitrate.is_some()
+            || self.preset.is_some()
+            || self.pixel_format.is_some()
+    }
+
+    /// Whether output in `format` keeps the alpha channel with these settings.
+    pub fn keeps_alpha(&self, format: VideoFormat) -> bool {
+        let codec = self.codec.or(VideoCodec::default_for(format));
+        let pixel_format_has_alpha = self.pixel_format.as_deref().is_none_or(|pixel_format| {
+            ["yuva", "rgba", "bgra", "argb", "abgr", "gbrap", "ya"]
+                .iter()
+                .any(|prefix| pixel_format.starts_with(prefix))
+        });
+        format.supports_alpha()
+            && codec.is_none_or(VideoCodec::supports_alpha)
+            && pixel_format_has_alpha
+    }
+}
+
+impl Default for EncoderSettings {
+    fn default() -> Self {
+        Self {
+            ffmpeg_input: FfmpegInput::default(),
+            loop_count: 0,
+            gif_colors: 256,
+            gif_dither: GifDither::default(),
+            codec: None,
+            crf: None,
+            bitrate: None,
+            preset: None,
+            pixel_format: None,
+            ffmpeg_args: Vec::new(),
+        }
+    }
+}
+
+/// Container and codec combination chosen from the output file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFormat {
+    /// H.264 in an MP4 container. Used for any unrecognized extension.
+    Mp4,
+    /// VP9 with an alpha plane in a WebM container (`.webm`).
+    WebM,
+    /// ProRes 4444 with an alpha plane in a QuickTime container (`.mov`).
+    ProRes,
+    /// Animated PNG (`.png` or `.apng`).
+    Apng,
+    /// Animated GIF (`.gif`).
+    Gif,
+    /// Animated WebP (`.webp`).
+    WebP,
+    /// AV1 in an IVF container (`.ivf`).
+    Ivf,
+}
+
+impl VideoFormat {
+    /// Pick the format matching the extension of `path`.
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("webm") => VideoFormat::WebM,
+            Some("mov") => VideoFormat::ProRes,
+            Some("png" | "apng") => VideoFormat::Apng,
+            Some("gif") => VideoFormat::Gif,
+            Some("webp") => VideoFormat::WebP,
+            Some("ivf") => VideoFormat::Ivf,
+            _ => VideoFormat::Mp4,
+        }
+    }
+
+    /// Whether the format keeps the alpha channel of the frames.
+    pub fn supports_alpha(self) -> bool {
+        matches!(
+            self,
+            VideoFormat::WebM | VideoFormat::ProRes | VideoFormat::Apng | VideoFormat::WebP
+        )
+    }
+}
+
+/// Open the sink that encodes `frame_count` frames into `output_path`.
+///
+/// Formats with an in-process encoder compiled in use it, everything else is
+/// handed to `ffmpeg`. Settings that [need `ffmpeg`](EncoderSettings::needs_ffmpeg)
+/// send every format to `ffmpeg`.
+#[cfg_attr(
+    not(all(feature = "ffmpeg", feature = "animation")),
+    allow(unused_variables)
+)]
+pub fn open_sink(
+    output_path: &Path,
+    framerate: u32,
+    frame_count: usize,
+    settings: &EncoderSettings,
+) -> Result<Box<dyn FrameSink>> {
+    match VideoFormat::from_path(output_path) {
+        #[cfg(feature = "animation")]
+        VideoFormat::Gif if !settings.needs_ffmpeg() => {
+            Ok(Box::new(GifSink::create(output_path, framerate, settings)?))
+        }
+        #[cfg(feature = "animation")]
+        VideoFormat::Apng if !settings.needs_ffmpeg() => Ok(Box::new(ApngSink::create(
+            output_path,
+            framerate,
+            frame_count,
+            settings,
+        )?)),
+        #[cfg(feature = "av1")]
+        VideoFormat::Ivf if !settings.needs_ffmpeg() => {
+            Ok(Box::new(Av1Sink::create(output_path, framerate)?))
+        }
+        #[cfg(feature = "ffmpeg")]
+        format => Ok(Box::new(FfmpegSink::new(
+            output_path,
+            framerate,
+            format,
+            settings,
+        )?)),
+        #[cfg(not(feature = "ffmpeg"))]
+        _ if settings.needs_ffmpeg() => Err(FaderError::EncoderMissing(
+            "encoder options need the `ffmpeg` feature".into(),
+        )),
+        #[cfg(not(feature = "ffmpeg"))]
+        format => Err(FaderError::EncoderMissing(format!(
+            "no encoder for {format:?} output, rebuild with the `ffmpeg` feature"
+        ))),
+    }
+}
+
+/// Render a frame for every step on `jobs` threads, write them to `sink` in
+/// order and finalize it. `render` and `render16` are given the index of the
+/// frame along with its step, and render it with 8 and 16 bits per channel
+/// for the depths `sink` takes. `jobs` of `0` uses every CPU core.
+///
+/// Frames are rendered in batches of a few per thread into buffers that are
+/// reused across batches, so memory use stays bounded.
+pub(crate) fn encode_frames<T: Sync>(
+    steps: &[T],
+    render: impl Fn(usize, &T, &mut RgbaImage) + Sync,
+    render16: impl Fn(usize, &T, &mut Rgba16Image) + Sync,
+    jobs: usize,
+    sink: &mut dyn FrameSink,
+) -> Result<()> {
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .map_err(|e| FaderError::Io(std::io::Error::other(e)))?;
+    let eight = sink.takes(BitDepth::Eight);
+    let sixteen = sink.takes(BitDepth::Sixteen);
+    let mut buffers =
+        vec![(RgbaImage::default(), Rgba16Image::default()); pool.current_num_threads() * 2];
+
+    for (batch_index, batch) in steps.chunks(buffers.len()).enumerate() {
+        let first = batch_index * buffers.len();
+        let frames = &mut buffers[..batch.len()];
+        pool.install(|| {
+            batch
+                .par_iter()
+                .zip(frames.par_iter_mut())
+                .enumerate()
+                .for_each(|(i, (step, (frame, frame16)))| {
+                    if eight {
+                        render(first + i, step, frame);
+                    }
+                    if sixteen {
+                        render16(first + i, step, frame16);
+                    }
+                })
+        });
+        for (frame, frame16) in frames.iter() {
+            if eight {
+                sink.write_frame(frame)?;
+            }
+            if sixteen {
+                sink.write_frame16(frame16)?;
+            }
+        }
+    }
+    sink.finish()
+}
+
+/// A builder rendering an animation frame by frame, such as a [`FadeJob`](crate::FadeJob).
+///
+/// Implementors describe what each frame is rendered from, and the provided
+/// methods render and encode the frames the same way for all of them.
+pub(crate) trait Renderer: Sync {
+    /// What a single frame is rendered from.
+    type Step: Sync;
+    /// What every frame is rendered from, such as the scaled images.
+    type Scene<'a>: Sync
+    where
+        Self: 'a;
+
+    /// Frames per second of the output.
+    fn framerate(&self) -> u32;
+
+    /// Options for the encoder used by [`write_video`](Self::write_video).
+    fn encoder_settings(&self) -> &EncoderSettings;
+
+    /// Number of threads rendering frames, `0` for every CPU core.
+    fn thread_count(&self) -> usize;
+
+    /// The step of every frame, in order, failing if the settings are invalid.
+    fn steps(&self) -> Result<Vec<Self::Step>>;
+
+    /// Number of frames rendered, failing if the settings are invalid.
+    fn frame_count(&self) -> Result<usize> {
+        Ok(self.steps()?.len())
+    }
+
+    /// Prepare what every frame is rendered from.
+    fn scene(&self) -> Self::Scene<'_>;
+
+    /// Render frame `index`, for `step` of `scene`, into `frame`.
+    fn render(
+        &self,
+        scene: &Self::Scene<'_>,
+        index: usize,
+        step: &Self::Step,
+        frame: &mut impl FrameBuffer,
+    );
+
+    /// Render the frames lazily, in order.
+    fn frames(&self) -> Result<impl Iterator<Item = RgbaImage> + '_> {
+        let steps = self.steps()?;
+        let scene = self.scene();
+        Ok(steps.into_iter().enumerate().map(move |(index, step)| {
+            let mut frame = RgbaImage::default();
+            self.render(&scene, index, &step, &mut frame);
+            frame
+        }))
+    }
+
+    /// Render every frame and encode them into a video at `output_path`.
+    fn write_video(&self, output_path: &Path) -> Result<()> {
+        // Validate the settings before creating the output.
+        let steps = self.steps()?;
+        let mut sink = open_sink(
+            output_path,
+            self.framerate(),
+            steps.len(),
+            self.encoder_settings(),
+        )?;
+        self.encode(&steps, sink.as_mut())
+    }
+
+    /// Render every frame into `sink` and finalize it.
+    fn write_to(&self, sink: &mut dyn FrameSink) -> Result<()> {
+        self.encode(&self.steps()?, sink)
+    }
+
+    /// Render a frame for each of `steps` into `sink` and finalize it.
+    fn encode(&self, steps: &[Self::Step], sink: &mut dyn FrameSink) -> Result<()> {
+        let scene = self.scene();
+        encode_frames(
+            steps,
+            |index, step, frame| self.render(&scene, index, step, frame),
+            |index, step, frame| self.render(&scene, index, step, frame),
+            self.thread_count(),
+            sink,
+        )
+    }
+}
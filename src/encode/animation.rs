@@ -0,0 +1,304 @@
+use color_quant::NeuQuant;
+use gif::{DisposalMethod, Encoder as GifEncoder, Frame, Repeat};
+use image::{RgbaImage, imageops};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use super::{EncoderSettings, FrameSink, GifDither};
+use crate::{FaderError, Result};
+
+/// Sampling factor of the NeuQuant quantizer, trading speed for quality.
+const QUANTIZER_SAMPLE_FACTOR: i32 = 10;
+
+/// Frame delays in hundredths of a second, as stored by GIF.
+///
+/// The rounding error is carried over between frames so the total length
+/// matches the frame rate even when it doesn't divide 100.
+struct GifDelays {
+    framerate: u32,
+    frame: u32,
+}
+
+impl Iterator for GifDelays {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<u16> {
+        let end = |frame: u32| (frame as f32 * 100.0 / self.framerate as f32).round() as u32;
+        let delay = end(self.frame + 1) - end(self.frame);
+        self.frame += 1;
+        Some(delay as u16)
+    }
+}
+
+/// Encodes frames into an animated GIF, quantizing each one to its own palette.
+///
+/// Pixels that are more than half transparent become fully transparent.
+pub struct GifSink {
+    output: Option<BufWriter<File>>,
+    encoder: Option<GifEncoder<BufWriter<File>>>,
+    delays: GifDelays,
+    loop_count: u16,
+    colors: u16,
+    dither: GifDither,
+}
+
+impl GifSink {
+    pub fn create(output_path: &Path, framerate: u32, settings: &EncoderSettings) -> Result<Self> {
+        Ok(Self {
+            output: Some(BufWriter::new(File::create(output_path)?)),
+            encoder: None,
+            delays: GifDelays {
+                framerate,
+                frame: 0,
+            },
+            loop_count: settings.loop_count,
+            colors: settings.gif_colors.clamp(2, 256),
+            dither: settings.gif_dither,
+        })
+    }
+
+    fn quantize(&self, frame: &RgbaImage) -> Result<Frame<'static>> {
+        let mut rgba = frame.clone();
+        let (width, height) = rgba.dimensions();
+        let has_transparency = rgba.pixels().any(|p| p[3] < 128);
+
+        // Quantize opaque colors only, keeping the last palette entry free
+        // for transparency when it is needed.
+        for pixel in rgba.pixels_mut() {
+            pixel[3] = 255;
+        }
+        let colors = self.colors as usize - has_transparency as usize;
+        let quantizer = NeuQuant::new(QUANTIZER_SAMPLE_FACTOR, colors, rgba.as_raw());
+        // Error diffusion in `image` indexes past the right edge of frames a
+        // single pixel wide.
+        if self.dither == GifDither::FloydSteinberg && width > 1 {
+            imageops::dither(&mut rgba, &quantizer);
+        }
+
+        let indices: Vec<u8> = rgba
+            .pixels()
+            .zip(frame.pixels())
+            .map(|(pixel, original)| {
+                if original[3] < 128 {
+                    colors as u8
+                } else {
+                    quantizer.index_of(&pixel.0) as u8
+                }
+            })
+            .collect();
+        let mut palette = quantizer.color_map_rgb();
+        if has_transparency {
+            palette.extend([0, 0, 0]);
+        }
+
+        let too_large = |_| {
+            FaderError::InvalidArgument(format!(
+                "GIF frames are at most 65535 pixels wide and high, got {width}x{height}"
+            ))
+        };
+        let width = u16::try_from(width).map_err(too_large)?;
+        let height = u16::try_from(height).map_err(too_large)?;
+        Ok(Frame::from_palette_pixels(
+            width,
+            height,
+            indices,
+            palette,
+            has_transparency.then_some(colors as u8),
+        ))
+    }
+}
+
+impl FrameSink for GifSink {
+    fn write_frame(&mut self, frame: &RgbaImage) -> Result<()> {
+        let mut gif_frame = self.quantize(frame)?;
+        gif_frame.delay = self.delays.next().unwrap_or_default();
+        // Clear each frame before the next so transparent pixels don't show
+        // the previous frame through.
+        gif_frame.dispose = DisposalMethod::Background;
+
+        if self.encoder.is_none() {
+            let output = self
+                .output
+                .take()
+                .expect("output is set until the first frame");
+            let mut encoder = GifEncoder::new(output, gif_frame.width, gif_frame.height, &[])
+                .map_err(|e| FaderError::encoder("GIF encoder", e))?;
+            // GIF counts repetitions after the first play, and a single play
+            // is expressed by leaving the loop extension out.
+            match self.loop_count {
+                0 => encoder.set_repeat(Repeat::Infinite),
+                1 => Ok(()),
+                n => encoder.set_repeat(Repeat::Finite(n - 1)),
+            }
+            .map_err(|e| FaderError::encoder("GIF encoder", e))?;
+            self.encoder = Some(encoder);
+        }
+
+        let encoder = self.encoder.as_mut().expect("encoder was created above");
+        encoder
+            .write_frame(&gif_frame)
+            .map_err(|e| FaderError::encoder("GIF encoder", e))
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        match self.encoder.take() {
+            Some(encoder) => {
+                let mut output = encoder.into_inner()?;
+                Ok(output.flush()?)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Encodes frames into an animated PNG.
+///
+/// APNG stores the frame count before the first frame, so it is given up
+/// front and frames are written as they come. Writing another number of
+/// frames fails.
+pub struct ApngSink {
+    output: Option<BufWriter<File>>,
+    writer: Option<png::Writer<BufWriter<File>>>,
+    frame_count: u32,
+    written: u32,
+    delay: u16,
+    loop_count: u16,
+}
+
+impl ApngSink {
+    pub fn create(
+        output_path: &Path,
+        framerate: u32,
+        frame_count: usize,
+        settings: &EncoderSettings,
+    ) -> Result<Self> {
+        let delay = u16::try_from(framerate).map_err(|_| {
+            FaderError::InvalidArgument(format!(
+                "APNG frame rates are at most 65535, got {framerate}"
+            ))
+        })?;
+        let frame_count = u32::try_from(frame_count).map_err(|_| {
+            FaderError::InvalidArgument(format!(
+                "APNG holds at most {} frames, got {frame_count}",
+                u32::MAX
+            ))
+        })?;
+        Ok(Self {
+            output: Some(BufWriter::new(File::create(output_path)?)),
+            writer: None,
+            frame_count,
+            written: 0,
+            delay,
+            loop_count: settings.loop_count,
+        })
+    }
+
+    fn wrong_frame_count(&self) -> FaderError {
+        FaderError::encoder(
+            "APNG encoder",
+            format!("expected {} frames", self.frame_count),
+        )
+    }
+
+    /// Start the file with the dimensions of the first frame.
+    fn write_header(&mut self, output: BufWriter<File>, first: &RgbaImage) -> Result<()> {
+        let mut encoder = png::Encoder::new(output, first.width(), first.height());
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .set_animated(self.frame_count, self.loop_count as u32)
+            .map_err(|e| FaderError::encoder("APNG encoder", e))?;
+        encoder
+            .set_frame_delay(1, self.delay)
+            .map_err(|e| FaderError::encoder("APNG encoder", e))?;
+        let writer = encoder
+            .write_header()
+            .map_err(|e| FaderError::encoder("APNG encoder", e))?;
+        self.writer = Some(writer);
+        Ok(())
+    }
+}
+
+impl FrameSink for ApngSink {
+    fn write_frame(&mut self, frame: &RgbaImage) -> Result<()> {
+        if self.written == self.frame_count {
+            return Err(self.wrong_frame_count());
+        }
+        self.written += 1;
+        if let Some(output) = self.output.take() {
+            self.write_header(output, frame)?;
+        }
+        let writer = self.writer.as_mut().expect("header was written above");
+        writer
+            .write_image_data(frame.as_raw())
+            .map_err(|e| FaderError::encoder("APNG encoder", e))
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.written != self.frame_count {
+            return Err(self.wrong_frame_count());
+        }
+        match self.writer.take() {
+            Some(writer) => writer
+                .finish()
+                .map_err(|e| FaderError::encoder("APNG encoder", e)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apng(frame_count: usize, frames: usize) -> Result<Vec<u8>> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fade.png");
+        let mut sink = ApngSink::create(&path, 10, frame_count, &EncoderSettings::default())?;
+        for _ in 0..frames {
+            sink.write_frame(&RgbaImage::new(2, 2))?;
+        }
+        sink.finish()?;
+        Ok(std::fs::read(path)?)
+    }
+
+    fn gif_delays(framerate: u32, frames: usize) -> Vec<u16> {
+        GifDelays {
+            framerate,
+            frame: 0,
+        }
+        .take(frames)
+        .collect()
+    }
+
+    #[test]
+    fn gif_delays_add_up_to_the_frame_rate() {
+        assert_eq!(gif_delays(10, 3), [10, 10, 10]);
+        assert_eq!(gif_delays(30, 6), [3, 4, 3, 3, 4, 3]);
+        for framerate in [1, 7, 24, 25, 30, 60, 144] {
+            let total: u32 = gif_delays(framerate, framerate as usize * 3)
+                .into_iter()
+                .map(u32::from)
+                .sum();
+            assert_eq!(total, 300, "{framerate} fps");
+        }
+    }
+
+    #[test]
+    fn apng_streams_the_announced_frames() {
+        let data = apng(3, 3).unwrap();
+        let decoder = png::Decoder::new(std::io::Cursor::new(data));
+        let reader = decoder.read_info().unwrap();
+        let control = reader.info().animation_control().unwrap();
+        assert_eq!(control.num_frames, 3);
+    }
+
+    #[test]
+    fn apng_rejects_another_number_of_frames() {
+        assert!(apng(3, 2).is_err());
+        assert!(apng(3, 4).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use image::RgbaImage;
+use rav1e::{
+    Config, Context, EncoderConfig, EncoderStatus,
+    color::{
+        ColorDescription, ColorPrimaries, MatrixCoefficients, PixelRange, TransferCharacteristics,
+    },
+    config::SpeedSettings,
+    data::Rational,
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use super::FrameSink;
+use crate::{FaderError, Result};
+
+/// Encodes frames to AV1 in-process with rav1e and stores them in an IVF file.
+///
+/// The alpha channel is dropped. The encoder is created on the first frame,
+/// once the dimensions are known.
+pub struct Av1Sink {
+    output: BufWriter<File>,
+    framerate: u32,
+    context: Option<Context<u8>>,
+    packet_count: u32,
+}
+
+impl Av1Sink {
+    pub fn create(output_path: &Path, framerate: u32) -> Result<Self> {
+        Ok(Self {
+            output: BufWriter::new(File::create(output_path)?),
+            framerate,
+            context: None,
+            packet_count: 0,
+        })
+    }
+
+    fn start(&mut self, width: u32, height: u32) -> Result<Context<u8>> {
+        let config = EncoderConfig {
+            width: width as usize,
+            height: height as usize,
+            time_base: Rational::new(1, self.framerate as u64),
+            speed_settings: SpeedSettings::from_preset(10),
+            pixel_range: PixelRange::Limited,
+            color_description: Some(ColorDescription {
+                color_primaries: ColorPrimaries::BT709,
+                transfer_characteristics: TransferCharacteristics::BT709,
+                matrix_coefficients: MatrixCoefficients::BT709,
+            }),
+            ..Default::default()
+        };
+        let context = Config::new()
+            .with_encoder_config(config)
+            .new_context()
+            .map_err(|e| {
+                FaderError::InvalidArgument(format!("unsupported by the AV1 encoder: {e}"))
+            })?;
+
+        // IVF file header. The frame count is patched in by `finish`.
+        self.output.write_all(b"DKIF")?;
+        self.output.write_all(&0u16.to_le_bytes())?;
+        self.output.write_all(&32u16.to_le_bytes())?;
+        self.output.write_all(b"AV01")?;
+        self.output.write_all(&(width as u16).to_le_bytes())?;
+        self.output.write_all(&(height as u16).to_le_bytes())?;
+        self.output.write_all(&self.framerate.to_le_bytes())?;
+        self.output.write_all(&1u32.to_le_bytes())?;
+        self.output.write_all(&0u32.to_le_bytes())?;
+        self.output.write_all(&0u32.to_le_bytes())?;
+
+        Ok(context)
+    }
+
+    /// Write every packet the encoder has ready to the IVF file.
+    fn drain(&mut self) -> Result<()> {
+        let Some(context) = self.context.as_mut() else {
+            return Ok(());
+        };
+        loop {
+            match context.receive_packet() {
+                Ok(packet) => {
+                    self.output
+                        .write_all(&(packet.data.len() as u32).to_le_bytes())?;
+                    self.output.write_all(&packet.input_frameno.to_le_bytes())?;
+                    self.output.write_all(&packet.data)?;
+                    self.packet_count += 1;
+                }
+                Err(EncoderStatus::Encoded) => continue,
+                Err(EncoderStatus::NeedMoreData | EncoderStatus::LimitReached) => return Ok(()),
+                Err(e) => return Err(FaderError::encoder("AV1 encoder", e)),
+            }
+        }
+    }
+}
+
+impl FrameSink for Av1Sink {
+    fn write_frame(&mut self, frame: &RgbaImage) -> Result<()> {
+        let (width, height) = frame.dimensions();
+        let mut context = match self.context.take() {
+            Some(context) => context,
+            None => self.start(width, height)?,
+        };
+
+        let (y, u, v) = rgb_to_yuv420(frame);
+        let chroma_width = width.div_ceil(2) as usize;
+        let mut input = context.new_frame();
+        input.planes[0].copy_from_raw_u8(&y, width as usize, 1);
+        input.planes[1].copy_from_raw_u8(&u, chroma_width, 1);
+        input.planes[2].copy_from_raw_u8(&v, chroma_width, 1);
+        context
+            .send_frame(input)
+            .map_err(|e| FaderError::encoder("AV1 encoder", e))?;
+
+        self.context = Some(context);
+        self.drain()
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if let Some(context) = self.context.as_mut() {
+            context.flush();
+        }
+        self.drain()?;
+
+        self.output.seek(SeekFrom::Start(24))?;
+        self.output.write_all(&self.packet_count.to_le_bytes())?;
+        Ok(self.output.flush()?)
+    }
+}
+
+/// Convert to limited-range BT.709 Y'CbCr with 2x2 chroma subsampling,
+/// ignoring the alpha channel.
+fn rgb_to_yuv420(rgb: &RgbaImage) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    let (width, height) = (rgb.width() as usize, rgb.height() as usize);
+    let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+    let mut y = Vec::with_capacity(width * height);
+    let mut cb = vec![0f32; chroma_width * chroma_height];
+    let mut cr = vec![0f32; chroma_width * chroma_height];
+    let mut samples = vec![0f32; chroma_width * chroma_height];
+
+    for (px, py, pixel) in rgb.enumerate_pixels() {
+        let [r, g, b, _] = pixel.0.map(|c| c as f32 / 255.0);
+        let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        y.push((16.0 + 219.0 * luma).round() as u8);
+
+        let index = (py as usize / 2) * chroma_width + px as usize / 2;
+        cb[index] += (b - luma) / 1.8556;
+        cr[index] += (r - luma) / 1.5748;
+        samples[index] += 1.0;
+    }
+
+    let to_chroma = |sum: &f32, count: &f32| (128.0 + 224.0 * sum / count).round() as u8;
+    let u = cb
+        .iter()
+        .zip(&samples)
+        .map(|(s, n)| to_chroma(s, n))
+        .collect();
+    let v = cr
+        .iter()
+        .zip(&samples)
+        .map(|(s, n)| to_chroma(s, n))
+        .collect();
+    (y, u, v)
+}
@@ -0,0 +1,539 @@
+use image::{ImageResult, RgbaImage};
+use std::{
+    borrow::Cow,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    thread::{self, JoinHandle},
+};
+use tempfile::{TempDir, tempdir};
+
+use super::{
+    BitDepth, EncoderSettings, FfmpegInput, FrameSink, Rgba16Image, VideoCodec, VideoFormat,
+};
+use crate::{FaderError, Result};
+
+/// Encodes frames with an external `ffmpeg` binary.
+///
+/// Frames are either streamed to `ffmpeg` as raw RGBA over stdin while they
+/// are rendered, or saved as numbered PNGs in a temporary directory that is
+/// encoded once finished. Pixel formats with more than 8 bits per channel are
+/// given frames with 16.
+#[derive(Debug)]
+pub struct FfmpegSink {
+    framerate: u32,
+    format: VideoFormat,
+    /// `None` for the animated image formats.
+    codec: Option<VideoCodec>,
+    settings: EncoderSettings,
+    output_path: PathBuf,
+    input: Input,
+}
+
+#[derive(Debug)]
+enum Input {
+    Pipe {
+        /// Started on the first frame, once the dimensions are known.
+        process: Option<Process>,
+    },
+    Png {
+        frames_dir: TempDir,
+        frame_count: usize,
+    },
+}
+
+impl FfmpegSink {
+    /// Prepare encoding into `output_path`, checking that the codec options
+    /// of `settings` suit the format.
+    pub fn new(
+        output_path: &Path,
+        framerate: u32,
+        format: VideoFormat,
+        settings: &EncoderSettings,
+    ) -> Result<Self> {
+        let codec = check_codec(format, settings)?;
+        let input = match settings.ffmpeg_input {
+            FfmpegInput::Pipe => Input::Pipe { process: None },
+            FfmpegInput::Png => Input::Png {
+                frames_dir: tempdir()?,
+                frame_count: 0,
+            },
+        };
+        Ok(Self {
+            framerate,
+            format,
+            codec,
+            settings: settings.clone(),
+            output_path: output_path.to_path_buf(),
+            input,
+        })
+    }
+
+    /// Output options passed to `ffmpeg` for the format, followed by the
+    /// extra arguments of the settings.
+    fn format_args(&self) -> Vec<String> {
+        let loop_count = self.settings.loop_count;
+        let plays = loop_count.to_string();
+        // The GIF muxer counts repetitions after the first play, -1 meaning none.
+        let repeats = match loop_count {
+            0 => "0".to_string(),
+            1 => "-1".to_string(),
+            n => (n - 1).to_string(),
+        };
+        let mut args = match self.codec {
+            Some(codec) => self.codec_args(codec),
+            None => {
+                let args: &[&str] = match self.format {
+                    VideoFormat::Apng => &["-f", "apng", "-plays", &plays],
+                    VideoFormat::Gif => &["-f", "gif", "-loop", &repeats],
+                    _ => &["-c:v", "libwebp_anim", "-loop", &plays],
+                };
+                args.iter().map(|arg| arg.to_string()).collect()
+            }
+        };
+        if self.format == VideoFormat::Ivf {
+            args.extend(["-f".into(), "ivf".into()]);
+        }
+        args.extend(self.settings.ffmpeg_args.iter().cloned());
+        args
+    }
+
+    /// Pixel format of video encoded with `codec`.
+    fn pixel_format(&self, codec: VideoCodec) -> &str {
+        let default_pixel_format = match codec {
+            VideoCodec::Vp9 if self.format == VideoFormat::WebM => "yuva420p",
+            VideoCodec::Prores => "yuva444p10le",
+            _ => "yuv420p",
+        };
+        (self.settings.pixel_format.as_deref()).unwrap_or(default_pixel_format)
+    }
+
+    /// Number of bits per channel of the frames `ffmpeg` is given.
+    fn depth(&self) -> BitDepth {
+        match self.codec {
+            Some(codec) if is_deep(self.pixel_format(codec)) => BitDepth::Sixteen,
+            _ => BitDepth::Eight,
+        }
+    }
+
+    /// Options encoding video with `codec`.
+    fn codec_args(&self, codec: VideoCodec) -> Vec<String> {
+        let pixel_format = self.pixel_format(codec);
+
+        let mut args: Vec<&str> = Vec::new();
+        // Chroma subsampling needs even dimensions, so odd ones lose their
+        // last row or column.
+        if pixel_format.contains("420") || pixel_format.contains("422") {
+            args.extend(["-vf", "crop=trunc(iw/2)*2:trunc(ih/2)*2"]);
+        }
+        args.extend(["-c:v", encoder_name(codec)]);
+        if codec == VideoCodec::Prores && pixel_format.contains("444") {
+            args.extend(["-profile:v", "4444"]);
+        }
+        args.extend(["-pix_fmt", pixel_format]);
+
+        let crf = self.settings.crf.map(|crf| crf.to_string());
+        if let Some(crf) = &crf {
+            args.extend(["-crf", crf]);
+            // libvpx and libaom only hold a constant quality when the
+            // bitrate is left unbounded.
+            let unbounded = matches!(codec, VideoCodec::Vp9 | VideoCodec::Av1);
+            if unbounded && self.settings.bitrate.is_none() {
+                args.extend(["-b:v", "0"]);
+            }
+        }
+        if let Some(bitrate) = &self.settings.bitrate {
+            args.extend(["-b:v", bitrate]);
+        }
+        if let Some(preset) = &self.settings.preset {
+            let option = match codec {
+                VideoCodec::Vp9 | VideoCodec::Av1 => "-cpu-used",
+                _ => "-preset",
+            };
+            args.extend([option, preset]);
+        }
+        args.into_iter().map(str::to_string).collect()
+    }
+
+    /// `ffmpeg` invocation with everything but the input options.
+    fn command(&self, input_args: &[&str]) -> Command {
+        let mut command = Command::new("ffmpeg");
+        command
+            .args(["-y", "-hide_banner", "-loglevel", "error"])
+            .args(["-framerate", &self.framerate.to_string()])
+            .args(input_args)
+            .args(self.format_args())
+            .arg(&self.output_path);
+        command
+    }
+}
+
+/// Whether `pixel_format` has more than 8 bits per channel, going by the
+/// `ffmpeg` naming scheme: `yuv420p10le` and `p010le` have 10 bits, and
+/// `rgba64le` has 64 bits per pixel.
+fn is_deep(pixel_format: &str) -> bool {
+    let Some(name) = (pixel_format.strip_suffix("le")).or_else(|| pixel_format.strip_suffix("be"))
+    else {
+        return false;
+    };
+    let digits = name.len() - name.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let bits = name[name.len() - digits..].parse::<u32>();
+    matches!(bits, Ok(9..=16 | 48 | 64))
+}
+
+/// Name of the `ffmpeg` encoder for `codec`.
+fn encoder_name(codec: VideoCodec) -> &'static str {
+    match codec {
+        VideoCodec::H264 => "libx264",
+        VideoCodec::H265 => "libx265",
+        VideoCodec::Vp9 => "libvpx-vp9",
+        VideoCodec::Av1 => "libaom-av1",
+        VideoCodec::Prores => "prores_ks",
+    }
+}
+
+/// The codec `format` is encoded with, after checking that the codec options
+/// of `settings` apply to it.
+fn check_codec(format: VideoFormat, settings: &EncoderSettings) -> Result<Option<VideoCodec>> {
+    let invalid = |message: String| Err(FaderError::InvalidArgument(message));
+    let Some(default) = VideoCodec::default_for(format) else {
+        if settings.has_codec_options() {
+            return invalid(format!("codec options do not apply to {format:?} output"));
+        }
+        return Ok(None);
+    };
+
+    let codec = settings.codec.unwrap_or(default);
+    if !codec.fits(format) {
+        return invalid(format!(
+            "{codec:?} video cannot be stored in {format:?} output"
+        ));
+    }
+    if let Some(crf) = settings.crf {
+        let max = match codec {
+            VideoCodec::H264 | VideoCodec::H265 => 51,
+            VideoCodec::Vp9 | VideoCodec::Av1 => 63,
+            VideoCodec::Prores => return invalid("ProRes has no CRF, use a bitrate".into()),
+        };
+        if crf > max {
+            return invalid(format!("{codec:?} CRF must be at most {max}, got {crf}"));
+        }
+    }
+    if let Some(bitrate) = &settings.bitrate {
+        let digits = bitrate.trim_end_matches(['k', 'K', 'm', 'M', 'g', 'G']);
+        if !digits
+            .parse::<f64>()
+            .is_ok_and(|n| n.is_finite() && n > 0.0)
+        {
+            return invalid(format!(
+                "bitrate must be a positive number with an optional k, M or G suffix, got `{bitrate}`"
+            ));
+        }
+    }
+    if settings.preset.is_some() && codec == VideoCodec::Prores {
+        return invalid("ProRes has no speed presets".into());
+    }
+    Ok(Some(codec))
+}
+
+/// A running `ffmpeg` whose stderr is collected in the background.
+#[derive(Debug)]
+struct Process {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stderr: JoinHandle<String>,
+}
+
+impl Process {
+    /// Start `command` with stderr captured, and stdin piped if `pipe_stdin`.
+    fn spawn(command: &mut Command, pipe_stdin: bool) -> Result<Self> {
+        let stdin = if pipe_stdin {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        };
+        let mut child = command
+            .stdin(stdin)
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => {
+                    FaderError::EncoderMissing("ffmpeg was not found on the PATH".into())
+                }
+                _ => FaderError::Io(e),
+            })?;
+
+        // Drain stderr on another thread so ffmpeg never blocks on a full pipe
+        // while frames are still being written to stdin.
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+        let stderr = thread::spawn(move || {
+            let mut output = String::new();
+            let _ = stderr.read_to_string(&mut output);
+            output
+        });
+
+        Ok(Self {
+            stdin: child.stdin.take(),
+            child,
+            stderr,
+        })
+    }
+
+    /// Close stdin and wait for `ffmpeg` to exit, turning a failure into an
+    /// error carrying what it printed.
+    fn wait(mut self) -> Result<()> {
+        drop(self.stdin.take());
+        let status = self.child.wait()?;
+        let stderr = self.stderr.join().unwrap_or_default();
+        if status.success() {
+            Ok(())
+        } else {
+            Err(FaderError::EncoderFailed {
+                encoder: "ffmpeg",
+                message: format!("{status}\n{}", stderr.trim_end()),
+            })
+        }
+    }
+}
+
+impl FfmpegSink {
+    /// Save or stream one frame of `width` by `height` pixels. `raw` is
+    /// streamed as `pixel_format`, while `save` writes the frame as a PNG.
+    fn write<'a>(
+        &mut self,
+        (width, height): (u32, u32),
+        pixel_format: &str,
+        save: impl FnOnce(&Path) -> ImageResult<()>,
+        raw: impl FnOnce() -> Cow<'a, [u8]>,
+    ) -> Result<()> {
+        if let Input::Png {
+            frames_dir,
+            frame_count,
+        } = &mut self.input
+        {
+            let path = frames_dir
+                .path()
+                .join(format!("frame_{:04}.png", frame_count));
+            save(&path).map_err(|e| FaderError::Io(io::Error::other(e)))?;
+            *frame_count += 1;
+            return Ok(());
+        }
+
+        if let Input::Pipe { process: None } = self.input {
+            let size = format!("{width}x{height}");
+            let mut command = self.command(&[
+                "-f",
+                "rawvideo",
+                "-pix_fmt",
+                pixel_format,
+                "-s",
+                &size,
+                "-i",
+                "-",
+            ]);
+            self.input = Input::Pipe {
+                process: Some(Process::spawn(&mut command, true)?),
+            };
+        }
+
+        let Input::Pipe {
+            process: Some(process),
+        } = &mut self.input
+        else {
+            unreachable!("ffmpeg is running");
+        };
+        let stdin = process.stdin.as_mut().expect("stdin is piped");
+        match stdin.write_all(&raw()) {
+            Ok(()) => Ok(()),
+            // ffmpeg quit early, its exit status and output explain why better
+            // than the pipe does.
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                let Input::Pipe {
+                    process: Some(process),
+                } = std::mem::replace(&mut self.input, Input::Pipe { process: None })
+                else {
+                    unreachable!("ffmpeg is running");
+                };
+                process.wait().and(Err(FaderError::Io(e)))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl FrameSink for FfmpegSink {
+    fn write_frame(&mut self, frame: &RgbaImage) -> Result<()> {
+        let raw = || Cow::Borrowed(frame.as_raw().as_slice());
+        self.write(frame.dimensions(), "rgba", |path| frame.save(path), raw)
+    }
+
+    fn write_frame16(&mut self, frame: &Rgba16Image) -> Result<()> {
+        let raw = || Cow::Owned(frame.iter().flat_map(|c| c.to_le_bytes()).collect());
+        self.write(frame.dimensions(), "rgba64le", |path| frame.save(path), raw)
+    }
+
+    fn takes(&self, depth: BitDepth) -> bool {
+        depth == self.depth()
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let process = match std::mem::replace(&mut self.input, Input::Pipe { process: None }) {
+            Input::Pipe { process: None } => return Ok(()),
+            Input::Pipe {
+                process: Some(process),
+            } => process,
+            Input::Png { frames_dir, .. } => {
+                let pattern = frames_dir.path().join("frame_%04d.png");
+                let mut command = self.command(&["-i", &pattern.to_string_lossy()]);
+                let process = Process::spawn(&mut command, false)?;
+                // Keep the frames around until ffmpeg is done with them.
+                let result = process.wait();
+                drop(frames_dir);
+                return result;
+            }
+        };
+        process.wait()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sink(format: VideoFormat, settings: EncoderSettings) -> Result<FfmpegSink> {
+        FfmpegSink::new(Path::new("out"), 30, format, &settings)
+    }
+
+    fn args(format: VideoFormat, settings: EncoderSettings) -> String {
+        sink(format, settings).unwrap().format_args().join(" ")
+    }
+
+    #[test]
+    fn checks_codec_options_against_the_format() {
+        let settings = |codec, crf| EncoderSettings {
+            codec,
+            crf,
+            ..EncoderSettings::default()
+        };
+        let check = |format, settings| check_codec(format, &settings);
+        assert_eq!(
+            check(VideoFormat::Mp4, settings(None, None)).unwrap(),
+            Some(VideoCodec::H264)
+        );
+        assert_eq!(check(VideoFormat::Gif, settings(None, None)).unwrap(), None);
+        assert!(check(VideoFormat::Gif, settings(None, Some(20))).is_err());
+        assert!(check(VideoFormat::WebM, settings(Some(VideoCodec::H264), None)).is_err());
+        assert!(check(VideoFormat::Mp4, settings(Some(VideoCodec::H265), Some(51))).is_ok());
+        assert!(check(VideoFormat::Mp4, settings(Some(VideoCodec::H265), Some(52))).is_err());
+        assert!(check(VideoFormat::WebM, settings(None, Some(63))).is_ok());
+        assert!(check(VideoFormat::ProRes, settings(None, Some(10))).is_err());
+    }
+
+    #[test]
+    fn checks_bitrates_and_presets() {
+        let bitrate = |bitrate: &str| EncoderSettings {
+            bitrate: Some(bitrate.into()),
+            ..EncoderSettings::default()
+        };
+        for valid in ["8M", "500k", "2.5G", "1000000"] {
+            assert!(
+                check_codec(VideoFormat::Mp4, &bitrate(valid)).is_ok(),
+                "{valid}"
+            );
+        }
+        for invalid in ["", "M", "-8M", "0k", "fast", "infk", "8MB"] {
+            assert!(
+                check_codec(VideoFormat::Mp4, &bitrate(invalid)).is_err(),
+                "{invalid}"
+            );
+        }
+        let preset = EncoderSettings {
+            preset: Some("slow".into()),
+            ..EncoderSettings::default()
+        };
+        assert!(check_codec(VideoFormat::Mp4, &preset).is_ok());
+        assert!(check_codec(VideoFormat::ProRes, &preset).is_err());
+    }
+
+    #[test]
+    fn builds_codec_arguments() {
+        assert_eq!(
+            args(VideoFormat::Mp4, EncoderSettings::default()),
+            "-vf crop=trunc(iw/2)*2:trunc(ih/2)*2 -c:v libx264 -pix_fmt yuv420p"
+        );
+        assert_eq!(
+            args(VideoFormat::ProRes, EncoderSettings::default()),
+            "-c:v prores_ks -profile:v 4444 -pix_fmt yuva444p10le"
+        );
+        let settings = EncoderSettings {
+            crf: Some(30),
+            preset: Some("4".into()),
+            ffmpeg_args: vec!["-row-mt".into(), "1".into()],
+            ..EncoderSettings::default()
+        };
+        assert_eq!(
+            args(VideoFormat::Ivf, settings),
+            "-vf crop=trunc(iw/2)*2:trunc(ih/2)*2 -c:v libaom-av1 -pix_fmt yuv420p -crf 30 \
+             -b:v 0 -cpu-used 4 -f ivf -row-mt 1"
+        );
+        let settings = EncoderSettings {
+            codec: Some(VideoCodec::H265),
+            bitrate: Some("8M".into()),
+            pixel_format: Some("yuv444p10le".into()),
+            ..EncoderSettings::default()
+        };
+        assert_eq!(
+            args(VideoFormat::Mp4, settings),
+            "-c:v libx265 -pix_fmt yuv444p10le -b:v 8M"
+        );
+    }
+
+    #[test]
+    fn builds_animation_arguments() {
+        let settings = |loop_count| EncoderSettings {
+            loop_count,
+            ..EncoderSettings::default()
+        };
+        assert_eq!(args(VideoFormat::Gif, settings(0)), "-f gif -loop 0");
+        assert_eq!(args(VideoFormat::Gif, settings(1)), "-f gif -loop -1");
+        assert_eq!(args(VideoFormat::Gif, settings(3)), "-f gif -loop 2");
+        assert_eq!(args(VideoFormat::Apng, settings(3)), "-f apng -plays 3");
+        assert_eq!(
+            args(VideoFormat::WebP, settings(0)),
+            "-c:v libwebp_anim -loop 0"
+        );
+    }
+
+    #[test]
+    fn deep_pixel_formats_take_16_bit_frames() {
+        for deep in [
+            "yuv420p10le",
+            "yuv444p12be",
+            "p010le",
+            "p016be",
+            "gray16le",
+            "rgba64le",
+        ] {
+            assert!(is_deep(deep), "{deep}");
+        }
+        for shallow in [
+            "yuv420p", "yuva444p", "rgb24", "nv12", "rgba", "gray", "p010",
+        ] {
+            assert!(!is_deep(shallow), "{shallow}");
+        }
+        let depth = |format, pixel_format: Option<&str>| {
+            let settings = EncoderSettings {
+                pixel_format: pixel_format.map(str::to_string),
+                ..EncoderSettings::default()
+            };
+            sink(format, settings).unwrap().depth()
+        };
+        assert_eq!(depth(VideoFormat::Mp4, None), BitDepth::Eight);
+        assert_eq!(depth(VideoFormat::ProRes, None), BitDepth::Sixteen);
+        assert_eq!(
+            depth(VideoFormat::WebM, Some("yuv420p10le")),
+            BitDepth::Sixteen
+        );
+        assert_eq!(depth(VideoFormat::Gif, None), BitDepth::Eight);
+    }
+}
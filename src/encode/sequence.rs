@@ -0,0 +1,300 @@
+use clap::ValueEnum;
+use image::{
+    ImageBuffer, ImageError, ImageFormat, Rgb, RgbaImage, buffer::ConvertBuffer,
+    codecs::jpeg::JpegEncoder,
+};
+use std::{
+    fmt,
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use super::{BitDepth, FrameSink, Rgba16Image};
+use crate::{FaderError, Result};
+
+/// File format of the frames of an image sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum FrameFormat {
+    /// 8-bit PNG with alpha.
+    #[default]
+    Png,
+    /// 16-bit PNG with alpha, rendered with 16 bits per channel.
+    Png16,
+    /// JPEG at the configured quality, dropping the alpha channel.
+    Jpeg,
+    /// Lossless WebP with alpha.
+    Webp,
+    /// Uncompressed 8-bit TIFF with alpha.
+    Tiff,
+}
+
+impl FrameFormat {
+    /// Extension of the frame files.
+    pub fn extension(self) -> &'static str {
+        match self {
+            FrameFormat::Png | FrameFormat::Png16 => "png",
+            FrameFormat::Jpeg => "jpg",
+            FrameFormat::Webp => "webp",
+            FrameFormat::Tiff => "tif",
+        }
+    }
+
+    /// Whether the format keeps the alpha channel of the frames.
+    pub fn supports_alpha(self) -> bool {
+        self != FrameFormat::Jpeg
+    }
+}
+
+/// Name of the frame files of an image sequence, without the extension.
+///
+/// `{frame}` stands for the number of the frame counting from 0, and
+/// `{frame:N}` for the number padded with zeros to at least `N` digits. The
+/// default is `frame_{frame:04}`, naming the frames `frame_0000`, `frame_0001`
+/// and so on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FramePattern {
+    prefix: String,
+    digits: usize,
+    suffix: String,
+}
+
+impl Default for FramePattern {
+    fn default() -> Self {
+        Self {
+            prefix: "frame_".into(),
+            digits: 4,
+            suffix: String::new(),
+        }
+    }
+}
+
+impl FramePattern {
+    /// File name of frame `index` in `format`.
+    pub fn file_name(&self, index: usize, format: FrameFormat) -> String {
+        format!(
+            "{}{index:0digits$}{}.{}",
+            self.prefix,
+            self.suffix,
+            format.extension(),
+            digits = self.digits
+        )
+    }
+}
+
+impl FromStr for FramePattern {
+    type Err = String;
+
+    /// Parse a pattern containing `{frame}` or `{frame:N}` exactly once.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(['/', '\\']) {
+            return Err(format!("`{s}` names a path, expected a file name"));
+        }
+        let Some(start) = s.find('{') else {
+            return Err(format!("`{s}` has no {{frame}} placeholder"));
+        };
+        let end = start
+            + s[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed `{{` in `{s}`"))?;
+        let digits = match &s[start + 1..end] {
+            "frame" => 0,
+            placeholder => placeholder
+                .strip_prefix("frame:")
+                .and_then(|digits| digits.parse::<usize>().ok())
+                .filter(|&digits| digits <= 20)
+                .ok_or_else(|| {
+                    format!(
+                        "unknown placeholder {{{placeholder}}}, expected {{frame}} or {{frame:N}}"
+                    )
+                })?,
+        };
+        let (prefix, suffix) = (&s[..start], &s[end + 1..]);
+        if suffix.contains(['{', '}']) || prefix.contains('}') {
+            return Err(format!("`{s}` may contain {{frame}} only once"));
+        }
+        Ok(Self {
+            prefix: prefix.into(),
+            digits,
+            suffix: suffix.into(),
+        })
+    }
+}
+
+impl fmt::Display for FramePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.digits {
+            0 => write!(f, "{}{{frame}}{}", self.prefix, self.suffix),
+            digits => write!(f, "{}{{frame:{digits}}}{}", self.prefix, self.suffix),
+        }
+    }
+}
+
+/// Saves every frame as a numbered image file in a directory, for tools that
+/// take image sequences rather than videos.
+///
+/// Frames already in the directory are overwritten when their names match.
+#[derive(Debug)]
+pub struct ImageSequenceSink {
+    dir: PathBuf,
+    format: FrameFormat,
+    pattern: FramePattern,
+    jpeg_quality: u8,
+    frame_count: usize,
+}
+
+impl ImageSequenceSink {
+    /// Save frames in `format` into `dir`, creating it if needed. Frames are
+    /// named after the default [`FramePattern`] and JPEG frames have a quality
+    /// of 90.
+    pub fn create(dir: &Path, format: FrameFormat) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            format,
+            pattern: FramePattern::default(),
+            jpeg_quality: 90,
+            frame_count: 0,
+        })
+    }
+
+    /// Name of the frame files.
+    pub fn pattern(mut self, pattern: FramePattern) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    /// Quality of JPEG frames, from 1 to 100.
+    pub fn jpeg_quality(mut self, quality: u8) -> Self {
+        self.jpeg_quality = quality.clamp(1, 100);
+        self
+    }
+
+    fn save(&self, frame: &RgbaImage, path: &Path) -> Result<(), ImageError> {
+        match self.format {
+            FrameFormat::Png => frame.save_with_format(path, ImageFormat::Png),
+            FrameFormat::Png16 => {
+                let wide: Rgba16Image = frame.convert();
+                wide.save_with_format(path, ImageFormat::Png)
+            }
+            FrameFormat::Jpeg => {
+                let rgb: ImageBuffer<Rgb<u8>, Vec<u8>> = frame.convert();
+                let file = BufWriter::new(File::create(path)?);
+                rgb.write_with_encoder(JpegEncoder::new_with_quality(file, self.jpeg_quality))
+            }
+            FrameFormat::Webp => frame.save_with_format(path, ImageFormat::WebP),
+            FrameFormat::Tiff => frame.save_with_format(path, ImageFormat::Tiff),
+        }
+    }
+
+    /// Save the next frame with `save`, numbering the file after it.
+    fn save_next(
+        &mut self,
+        save: impl FnOnce(&Self, &Path) -> Result<(), ImageError>,
+    ) -> Result<()> {
+        let path = self
+            .dir
+            .join(self.pattern.file_name(self.frame_count, self.format));
+        save(self, &path).map_err(|e| match e {
+            ImageError::IoError(e) => FaderError::Io(e),
+            e => FaderError::encoder("image sequence", format!("{}: {e}", path.display())),
+        })?;
+        self.frame_count += 1;
+        Ok(())
+    }
+}
+
+impl FrameSink for ImageSequenceSink {
+    fn write_frame(&mut self, frame: &RgbaImage) -> Result<()> {
+        self.save_next(|sink, path| sink.save(frame, path))
+    }
+
+    fn write_frame16(&mut self, frame: &Rgba16Image) -> Result<()> {
+        self.save_next(|_, path| frame.save_with_format(path, ImageFormat::Png))
+    }
+
+    fn takes(&self, depth: BitDepth) -> bool {
+        let deep = self.format == FrameFormat::Png16;
+        depth
+            == if deep {
+                BitDepth::Sixteen
+            } else {
+                BitDepth::Eight
+            }
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn names_frames_after_the_pattern() {
+        let pattern = FramePattern::default();
+        assert_eq!(pattern.file_name(7, FrameFormat::Png), "frame_0007.png");
+        assert_eq!(
+            pattern.file_name(12345, FrameFormat::Tiff),
+            "frame_12345.tif"
+        );
+
+        let pattern: FramePattern = "bg.{frame:02}-x".parse().unwrap();
+        assert_eq!(pattern.file_name(3, FrameFormat::Jpeg), "bg.03-x.jpg");
+        let pattern: FramePattern = "{frame}".parse().unwrap();
+        assert_eq!(pattern.file_name(42, FrameFormat::Png16), "42.png");
+    }
+
+    #[test]
+    fn patterns_round_trip() {
+        for pattern in [
+            "frame_{frame:4}",
+            "{frame}",
+            "a{frame}b",
+            "shot-{frame:1}.final",
+        ] {
+            assert_eq!(
+                pattern.parse::<FramePattern>().unwrap().to_string(),
+                pattern
+            );
+        }
+        let padded: FramePattern = "frame_{frame:04}".parse().unwrap();
+        assert_eq!(padded, FramePattern::default());
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        for invalid in [
+            "frame",
+            "frame_{frame",
+            "{frame}{frame}",
+            "{frame}}",
+            "}{frame}",
+            "{index}",
+            "{frame:x}",
+            "{frame:21}",
+            "dir/{frame}",
+            "dir\\{frame}",
+        ] {
+            assert!(invalid.parse::<FramePattern>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn png16_frames_take_16_bits() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut sink = ImageSequenceSink::create(dir.path(), FrameFormat::Png16).unwrap();
+        assert!(sink.takes(BitDepth::Sixteen) && !sink.takes(BitDepth::Eight));
+        sink.write_frame16(&Rgba16Image::from_pixel(2, 1, Rgba([1, 2, 3, 65535])))
+            .unwrap();
+        let frame = image::open(dir.path().join("frame_0000.png")).unwrap();
+        assert_eq!(frame.into_rgba16().get_pixel(1, 0), &Rgba([1, 2, 3, 65535]));
+
+        let sink = ImageSequenceSink::create(dir.path(), FrameFormat::Png).unwrap();
+        assert!(sink.takes(BitDepth::Eight) && !sink.takes(BitDepth::Sixteen));
+    }
+}
@@ -0,0 +1,73 @@
+use image::{DynamicImage, ImageError};
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// Everything that can go wrong while fading an image into a video.
+#[derive(Debug, Error)]
+pub enum FaderError {
+    /// An input image could not be read or decoded.
+    #[error("failed to open image {}: {source}", path.display())]
+    ImageDecode {
+        path: PathBuf,
+        #[source]
+        source: ImageError,
+    },
+
+    /// Reading or writing a file failed.
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    /// The encoder needed for the output format is not available.
+    #[error("encoder not available: {0}")]
+    EncoderMissing(String),
+
+    /// The encoder rejected the frames or exited unsuccessfully. For `ffmpeg`
+    /// the message includes what it printed to stderr.
+    #[error("{encoder} failed: {message}")]
+    EncoderFailed {
+        encoder: &'static str,
+        message: String,
+    },
+
+    /// The requested fade cannot be produced with the given settings.
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+
+    /// A keyframe timeline file is malformed or describes an impossible fade.
+    #[error("invalid timeline {}: {message}", path.display())]
+    InvalidTimeline { path: PathBuf, message: String },
+}
+
+impl FaderError {
+    /// Wrap an error reported by `encoder`.
+    pub(crate) fn encoder(encoder: &'static str, error: impl fmt::Display) -> Self {
+        FaderError::EncoderFailed {
+            encoder,
+            message: error.to_string(),
+        }
+    }
+
+    /// Process exit code for the error, distinct for every kind of failure.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            FaderError::InvalidArgument(_) | FaderError::InvalidTimeline { .. } => 2,
+            FaderError::ImageDecode { .. } => 3,
+            FaderError::Io(_) => 4,
+            FaderError::EncoderMissing(_) => 5,
+            FaderError::EncoderFailed { .. } => 6,
+        }
+    }
+}
+
+pub type Result<T, E = FaderError> = std::result::Result<T, E>;
+
+/// Open and decode the image at `path`, reporting failures with the path.
+pub(crate) fn open_image(path: &Path) -> Result<DynamicImage> {
+    image::open(path).map_err(|source| FaderError::ImageDecode {
+        path: path.to_path_buf(),
+        source,
+    })
+}
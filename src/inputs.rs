@@ -0,0 +1,169 @@
+use image::ImageFormat;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{FaderError, Result};
+
+/// Expand directories and glob patterns among `inputs` into the image files
+/// they name, keeping the order of `inputs`.
+///
+/// A directory stands for the images directly inside it, sorted by name and
+/// leaving out hidden files. A path that does not exist but contains `*`, `?`
+/// or `[...]` is matched against the file system, with wildcards allowed in
+/// any component; like in a shell, wildcards do not match a leading `.`.
+/// Other paths are passed through unchanged, whether they exist or not.
+pub fn expand_inputs(inputs: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut expanded = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        // A file whose name happens to contain wildcards is taken literally.
+        if !input.exists() && is_pattern(&input.to_string_lossy()) {
+            let matches = glob(input)?;
+            if matches.is_empty() {
+                return Err(FaderError::InvalidArgument(format!(
+                    "no images match {}",
+                    input.display()
+                )));
+            }
+            expanded.extend(matches);
+        } else if input.is_dir() {
+            let images = images_in(input)?;
+            if images.is_empty() {
+                return Err(FaderError::InvalidArgument(format!(
+                    "no images found in {}",
+                    input.display()
+                )));
+            }
+            expanded.extend(images);
+        } else {
+            expanded.push(input.clone());
+        }
+    }
+    Ok(expanded)
+}
+
+fn is_pattern(text: &str) -> bool {
+    text.contains(['*', '?', '['])
+}
+
+/// Whether `path` has the extension of an image format that can be decoded.
+fn is_image(path: &Path) -> bool {
+    path.is_file() && ImageFormat::from_path(path).is_ok_and(|format| format.reading_enabled())
+}
+
+/// The images directly inside `dir` that are not hidden, sorted by name.
+fn images_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut images = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if is_image(&path) {
+            images.push(path);
+        }
+    }
+    images.sort();
+    Ok(images)
+}
+
+/// The images matching `pattern`, sorted by name within every directory.
+/// Directories that cannot be read are skipped.
+fn glob(pattern: &Path) -> Result<Vec<PathBuf>> {
+    let pattern = pattern.to_string_lossy();
+    let options = glob::MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: true,
+    };
+    let paths = glob::glob_with(&pattern, options).map_err(|e| {
+        FaderError::InvalidArgument(format!("invalid pattern {pattern}: {}", e.msg))
+    })?;
+    Ok(paths
+        .filter_map(|path| path.ok())
+        .filter(|path| is_image(path))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory holding empty files and subdirectories named `names`, the
+    /// subdirectories ending in `/`.
+    fn tree(names: &[&str]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for name in names {
+            let path = dir.path().join(name);
+            if name.ends_with('/') {
+                fs::create_dir_all(path).unwrap();
+            } else {
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, b"").unwrap();
+            }
+        }
+        dir
+    }
+
+    fn expand(dir: &Path, inputs: &[&str]) -> Result<Vec<String>> {
+        let inputs: Vec<PathBuf> = inputs.iter().map(|input| dir.join(input)).collect();
+        Ok(expand_inputs(&inputs)?
+            .iter()
+            .map(|path| {
+                let relative = path.strip_prefix(dir).unwrap();
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect())
+    }
+
+    #[test]
+    fn existing_files_are_never_patterns() {
+        let dir = tree(&["photo[1].ppm", "photo1.ppm"]);
+        assert_eq!(
+            expand(dir.path(), &["photo[1].ppm"]).unwrap(),
+            ["photo[1].ppm"]
+        );
+        assert_eq!(expand(dir.path(), &["photo[1]*"]).unwrap(), ["photo1.ppm"]);
+    }
+
+    #[test]
+    fn patterns_match_images_in_order() {
+        let dir = tree(&[
+            "b.png",
+            "a.jpg",
+            "notes.txt",
+            ".hidden.png",
+            "sub/c.png",
+            "sub/d.gif",
+            "other/e.png",
+        ]);
+        assert_eq!(expand(dir.path(), &["*"]).unwrap(), ["a.jpg", "b.png"]);
+        assert_eq!(
+            expand(dir.path(), &["*/*.png"]).unwrap(),
+            ["other/e.png", "sub/c.png"]
+        );
+        assert_eq!(
+            expand(dir.path(), &["sub/[!c].*", "?.png"]).unwrap(),
+            ["sub/d.gif", "b.png"]
+        );
+        assert!(expand(dir.path(), &["*.webp"]).is_err());
+    }
+
+    #[test]
+    fn directories_and_missing_paths() {
+        let dir = tree(&["sub/b.png", "sub/a.png", "sub/.c.png", "empty/"]);
+        assert_eq!(
+            expand(dir.path(), &["sub", "missing.png"]).unwrap(),
+            ["sub/a.png", "sub/b.png", "missing.png"]
+        );
+        assert!(expand(dir.path(), &["empty"]).is_err());
+    }
+
+    #[test]
+    fn pathological_patterns_finish() {
+        let dir = tree(&[&format!("{}.png", "a".repeat(40))]);
+        assert!(expand(dir.path(), &["a*a*a*a*a*a*a*a*a*a*b.png"]).is_err());
+    }
+}
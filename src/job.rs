@@ -0,0 +1,333 @@
+use image::{DynamicImage, RgbaImage};
+use std::{borrow::Cow, path::Path, sync::Arc};
+
+use crate::{
+    Color, ColorFade, ColorSpace, Dissolve, Dither, Easing, Effect, EncoderSettings, FadeStyle,
+    FadeTimeline, FrameBuffer, FrameSink, LumaMatte, Repeat, Result, Scaling, Wipe,
+    apply_effect_into, encode::Renderer, error::open_image, fade_image_into, fade_opacity_into,
+    matte::Matte, render::fade_matte_into,
+};
+
+/// Fade that changes pixels at different times rather than all at once.
+#[derive(Debug, Clone)]
+enum Transition {
+    Wipe(Wipe),
+    LumaMatte(LumaMatte),
+    Dissolve(Dissolve),
+}
+
+/// Builder describing a single fade of one image.
+///
+/// ```no_run
+/// use fader::{FadeJob, FadeStyle};
+///
+/// let job = FadeJob::open("background.png")?
+///     .framerate(30)
+///     .duration(4.0)
+///     .style(FadeStyle::ToDarkAndBack);
+/// job.write_video("background.mp4")?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct FadeJob {
+    image: RgbaImage,
+    framerate: u32,
+    duration: f32,
+    hold_start: f32,
+    hold_middle: f32,
+    hold_end: f32,
+    looping: bool,
+    repeat: Repeat,
+    style: FadeStyle,
+    transition: Option<Transition>,
+    easing: Easing,
+    effect: Option<Arc<dyn Effect>>,
+    color: Color,
+    colorspace: ColorSpace,
+    dither: Dither,
+    fade_alpha: bool,
+    scaling: Option<Scaling>,
+    encoder: EncoderSettings,
+    jobs: usize,
+}
+
+impl FadeJob {
+    /// Start a job for an already decoded image, at 10 fps for 2 seconds.
+    pub fn new(image: DynamicImage) -> Self {
+        Self {
+            image: image.into_rgba8(),
+            framerate: 10,
+            duration: 2.0,
+            hold_start: 0.0,
+            hold_middle: 0.0,
+            hold_end: 0.0,
+            looping: false,
+            repeat: Repeat::default(),
+            style: FadeStyle::default(),
+            transition: None,
+            easing: Easing::default(),
+            effect: None,
+            color: Color::BLACK,
+            colorspace: ColorSpace::default(),
+            dither: Dither::default(),
+            fade_alpha: false,
+            scaling: None,
+            encoder: EncoderSettings::default(),
+            jobs: 0,
+        }
+    }
+
+    /// Decode the image at `path` and start a job for it.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(open_image(path.as_ref())?))
+    }
+
+    /// Frame rate of the output video.
+    pub fn framerate(mut self, framerate: u32) -> Self {
+        self.framerate = framerate;
+        self
+    }
+
+    /// Duration of the fade effect in seconds.
+    pub fn duration(mut self, duration: f32) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Time in seconds the image stays static before the fade begins.
+    pub fn hold_start(mut self, hold_start: f32) -> Self {
+        self.hold_start = hold_start;
+        self
+    }
+
+    /// Time in seconds the fade pauses at its turnaround, for styles that
+    /// [turn around](FadeStyle::turns_around).
+    pub fn hold_middle(mut self, hold_middle: f32) -> Self {
+        self.hold_middle = hold_middle;
+        self
+    }
+
+    /// Time in seconds the last frame stays static after the fade ends.
+    pub fn hold_end(mut self, hold_end: f32) -> Self {
+        self.hold_end = hold_end;
+        self
+    }
+
+    /// Make the fade a seamless loop, see [`FadeTimeline::looping`].
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// How often the fade plays in the output. Plays once by default.
+    pub fn repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Style of the fade effect.
+    pub fn style(mut self, style: FadeStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Sweep the fade across the frame instead of fading the whole image at
+    /// once. The style still decides whether the wipe covers or uncovers it.
+    /// Replaces any [luma matte](Self::luma_matte) or [dissolve](Self::dissolve).
+    pub fn wipe(mut self, wipe: Wipe) -> Self {
+        self.transition = Some(Transition::Wipe(wipe));
+        self
+    }
+
+    /// Fade every pixel at the time given by the luminance of a mask, dark
+    /// areas first. Replaces any [wipe](Self::wipe) or [dissolve](Self::dissolve).
+    pub fn luma_matte(mut self, luma_matte: LumaMatte) -> Self {
+        self.transition = Some(Transition::LumaMatte(luma_matte));
+        self
+    }
+
+    /// Dissolve the image grain by grain in the order of a noise field. The
+    /// style still decides whether the grains appear or disappear. Replaces
+    /// any [wipe](Self::wipe) or [luma matte](Self::luma_matte).
+    pub fn dissolve(mut self, dissolve: Dissolve) -> Self {
+        self.transition = Some(Transition::Dissolve(dissolve));
+        self
+    }
+
+    /// Easing curve applied to every ramp of the fade.
+    pub fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Animate `effect` instead of fading to the [color](Self::color), such as
+    /// [desaturating](crate::Desaturate) the image. Ignored when the job
+    /// [fades alpha](Self::fade_alpha).
+    pub fn effect(mut self, effect: impl Effect + 'static) -> Self {
+        self.effect = Some(Arc::new(effect));
+        self
+    }
+
+    /// Color the image fades to or from. Defaults to black.
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Color space in which pixels are interpolated.
+    pub fn colorspace(mut self, colorspace: ColorSpace) -> Self {
+        self.colorspace = colorspace;
+        self
+    }
+
+    /// Sets the [`Dither`] mode.
+    pub fn dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    /// Fade the alpha channel to transparency instead of blending towards the
+    /// fade color. Needs an output format that [supports alpha](crate::VideoFormat::supports_alpha).
+    pub fn fade_alpha(mut self, fade_alpha: bool) -> Self {
+        self.fade_alpha = fade_alpha;
+        self
+    }
+
+    /// Scale the image to a fixed output size. By default the video has the
+    /// dimensions of the image.
+    pub fn scaling(mut self, scaling: Scaling) -> Self {
+        self.scaling = Some(scaling);
+        self
+    }
+
+    /// The source image being faded, before scaling.
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    /// The validated length of the fade, its holds and repeats.
+    pub fn timeline(&self) -> Result<FadeTimeline> {
+        FadeTimeline::new(self.duration, self.framerate)?
+            .holds(self.hold_start, self.hold_middle, self.hold_end)?
+            .looping(self.looping)
+            .repeat(self.repeat)
+    }
+
+    /// Brightness factor applied to each frame, in order.
+    pub fn fade_factors(&self) -> Result<Vec<f32>> {
+        Ok(self.timeline()?.fade_factors(self.style, self.easing))
+    }
+
+    /// The image scaled to the output size.
+    fn scaled_image(&self) -> Cow<'_, RgbaImage> {
+        match &self.scaling {
+            Some(scaling) => Cow::Owned(scaling.apply(&self.image)),
+            None => Cow::Borrowed(&self.image),
+        }
+    }
+
+    /// The order the pixels of `image` are faded in, unless they all fade at once.
+    fn matte(&self, image: &RgbaImage) -> Option<Matte> {
+        let (width, height) = image.dimensions();
+        Some(match self.transition.as_ref()? {
+            Transition::Wipe(wipe) => wipe.matte(width, height),
+            Transition::LumaMatte(luma_matte) => luma_matte.matte(width, height),
+            Transition::Dissolve(dissolve) => dissolve.matte(width, height),
+        })
+    }
+
+    /// Render the frames of the fade lazily, in order.
+    pub fn frames(&self) -> Result<impl Iterator<Item = RgbaImage> + '_> {
+        Renderer::frames(self)
+    }
+
+    /// Number of frames of the video, holds and repeats included.
+    pub fn frame_count(&self) -> Result<usize> {
+        Renderer::frame_count(self)
+    }
+
+    /// Options for the encoder used by [`write_video`](Self::write_video).
+    pub fn encoder(mut self, encoder: EncoderSettings) -> Self {
+        self.encoder = encoder;
+        self
+    }
+
+    /// Number of threads rendering frames. `0`, the default, uses every CPU core.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Render every frame and encode them into a video at `output_path`.
+    ///
+    /// The container and codec follow the extension of `output_path`, see
+    /// [`VideoFormat`](crate::VideoFormat).
+    pub fn write_video(&self, output_path: impl AsRef<Path>) -> Result<()> {
+        Renderer::write_video(self, output_path.as_ref())
+    }
+
+    /// Render every frame into `sink` and finalize it.
+    pub fn write_to(&self, sink: &mut dyn FrameSink) -> Result<()> {
+        Renderer::write_to(self, sink)
+    }
+}
+
+impl Renderer for FadeJob {
+    type Step = f32;
+    /// The scaled image and the order its pixels fade in.
+    type Scene<'a> = (Cow<'a, RgbaImage>, Option<Matte>);
+
+    fn framerate(&self) -> u32 {
+        self.framerate
+    }
+
+    fn encoder_settings(&self) -> &EncoderSettings {
+        &self.encoder
+    }
+
+    fn thread_count(&self) -> usize {
+        self.jobs
+    }
+
+    fn steps(&self) -> Result<Vec<f32>> {
+        self.fade_factors()
+    }
+
+    fn frame_count(&self) -> Result<usize> {
+        Ok(self.timeline()?.frame_count(self.style))
+    }
+
+    fn scene(&self) -> Self::Scene<'_> {
+        let image = self.scaled_image();
+        let matte = self.matte(&image);
+        (image, matte)
+    }
+
+    fn render(
+        &self,
+        (image, matte): &Self::Scene<'_>,
+        index: usize,
+        &factor: &f32,
+        frame: &mut impl FrameBuffer,
+    ) {
+        if let Some(matte) = matte {
+            let color_fade = ColorFade::new(self.color, self.colorspace);
+            let effect = (!self.fade_alpha).then(|| self.effect.as_deref().unwrap_or(&color_fade));
+            fade_matte_into(image, matte, factor, effect, self.dither, index, frame)
+        } else if self.fade_alpha {
+            fade_opacity_into(image, factor, self.dither, index, frame)
+        } else if let Some(effect) = &self.effect {
+            apply_effect_into(image, effect.as_ref(), factor, self.dither, index, frame)
+        } else {
+            fade_image_into(
+                image,
+                factor,
+                self.color,
+                self.colorspace,
+                self.dither,
+                index,
+                frame,
+            )
+        }
+    }
+}
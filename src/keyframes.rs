@@ -0,0 +1,641 @@
+use image::{DynamicImage, RgbaImage};
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Color, ColorSpace, Dither, Easing, EncoderSettings, FaderError, FrameBuffer, FrameSink, Result,
+    Scaling, blend_images_into,
+    dither::Quantize,
+    encode::Renderer,
+    error::open_image,
+    fade_image_into, fade_opacity_into,
+    render::{copy_into, match_dimensions, pixels},
+    scale, timeline,
+};
+
+mod parse;
+
+use parse::Document;
+
+/// A point on a keyframe timeline.
+///
+/// Between two keyframes every setting is interpolated, shaped by the easing
+/// of the later one. Where the image changes, the two images dissolve into
+/// each other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframe {
+    /// Time of the keyframe in seconds from the start of the video.
+    pub time: f32,
+    /// Brightness of the image, from 0 showing only the fade color to 1
+    /// leaving the image untouched.
+    pub brightness: f32,
+    /// Opacity of the image, from 0 for fully transparent to 1 for opaque.
+    pub opacity: f32,
+    /// Curve of the transition from the previous keyframe to this one.
+    pub easing: Easing,
+    /// Color the image fades towards as its brightness drops.
+    pub color: Color,
+    /// Image shown at this keyframe, `None` meaning the main image of the job.
+    pub image: Option<PathBuf>,
+}
+
+impl Keyframe {
+    /// A keyframe at `time` showing the main image untouched.
+    pub fn new(time: f32) -> Self {
+        Self {
+            time,
+            brightness: 1.0,
+            opacity: 1.0,
+            easing: Easing::default(),
+            color: Color::BLACK,
+            image: None,
+        }
+    }
+}
+
+/// Read the keyframes described by a `.toml` or `.json` file.
+///
+/// The file holds a `keyframes` array of tables, each with a `time` in seconds
+/// and optionally `brightness`, `opacity`, `easing`, `color` and `image`.
+/// Settings other than `easing` carry over from the previous keyframe when
+/// left out; `easing` and the initial `color` default to top-level `easing`
+/// and `color` keys. Image paths are relative to the file.
+///
+/// ```toml
+/// color = "black"
+///
+/// [[keyframes]]
+/// time = 5
+///
+/// [[keyframes]]
+/// time = 7
+/// brightness = 0
+/// easing = "ease-in"
+/// ```
+pub fn read_keyframes(path: impl AsRef<Path>) -> Result<Vec<Keyframe>> {
+    let path = path.as_ref();
+    let invalid = |message: String| FaderError::InvalidTimeline {
+        path: path.to_path_buf(),
+        message,
+    };
+
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    let parse = match extension.as_deref() {
+        Some("json") => parse::json,
+        Some("toml") => parse::toml,
+        _ => return Err(invalid("expected a .toml or .json file".into())),
+    };
+    let text = std::fs::read_to_string(path)?;
+    let document = parse(&text).map_err(invalid)?;
+
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    let keyframes = decode(document, base_dir).map_err(invalid)?;
+    validate(&keyframes).map_err(invalid)?;
+    Ok(keyframes)
+}
+
+/// Turn the parsed document into keyframes, filling in carried-over settings.
+fn decode(document: Document, base_dir: &Path) -> Result<Vec<Keyframe>, String> {
+    let easing = match &document.easing {
+        Some(easing) => parse_str(easing, "easing")?,
+        None => Easing::default(),
+    };
+    let mut previous = Keyframe {
+        easing,
+        ..Keyframe::new(0.0)
+    };
+    if let Some(color) = &document.color {
+        previous.color = parse_str(color, "color")?;
+    }
+
+    let mut keyframes = Vec::with_capacity(document.keyframes.len());
+    for (index, entry) in document.keyframes.into_iter().enumerate() {
+        let at = |message: String| format!("keyframe {}: {message}", index + 1);
+        let mut keyframe = Keyframe {
+            time: entry.time,
+            easing,
+            ..previous
+        };
+        if let Some(brightness) = entry.brightness {
+            keyframe.brightness = brightness;
+        }
+        if let Some(opacity) = entry.opacity {
+            keyframe.opacity = opacity;
+        }
+        if let Some(easing) = &entry.easing {
+            keyframe.easing = parse_str(easing, "easing").map_err(at)?;
+        }
+        if let Some(color) = &entry.color {
+            keyframe.color = parse_str(color, "color").map_err(at)?;
+        }
+        if let Some(image) = entry.image {
+            keyframe.image = Some(base_dir.join(image));
+        }
+        previous = keyframe.clone();
+        keyframes.push(keyframe);
+    }
+    Ok(keyframes)
+}
+
+fn parse_str<T: std::str::FromStr<Err = String>>(value: &str, key: &str) -> Result<T, String> {
+    value.parse().map_err(|e| format!("`{key}`: {e}"))
+}
+
+/// Check that the keyframes describe a fade that can be rendered.
+fn validate(keyframes: &[Keyframe]) -> Result<(), String> {
+    if keyframes.is_empty() {
+        return Err("at least one keyframe is needed".into());
+    }
+    let mut last_time = 0.0;
+    for (index, keyframe) in keyframes.iter().enumerate() {
+        let at = |message: &str| format!("keyframe {}: {message}", index + 1);
+        if !keyframe.time.is_finite() || keyframe.time < 0.0 {
+            return Err(at("`time` must be a non-negative number of seconds"));
+        }
+        // Even at 1 fps, later keyframes would need more than the most frames.
+        if keyframe.time > timeline::MAX_FRAMES as f32 {
+            return Err(at(&format!(
+                "`time` must be at most {} seconds",
+                timeline::MAX_FRAMES
+            )));
+        }
+        if keyframe.time < last_time {
+            return Err(at("`time` is earlier than the previous keyframe"));
+        }
+        if !(0.0..=1.0).contains(&keyframe.brightness) {
+            return Err(at("`brightness` must be between 0 and 1"));
+        }
+        if !(0.0..=1.0).contains(&keyframe.opacity) {
+            return Err(at("`opacity` must be between 0 and 1"));
+        }
+        last_time = keyframe.time;
+    }
+    Ok(())
+}
+
+/// How one frame of a keyframe timeline is rendered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Step {
+    from: usize,
+    to: usize,
+    blend: f32,
+    brightness: f32,
+    opacity: f32,
+    color: Color,
+}
+
+/// Builder rendering a fade described by [keyframes](Keyframe).
+///
+/// Without [scaling](Self::scaling), keyframes showing other images than the
+/// main one have them resized to the dimensions of the main image.
+///
+/// ```no_run
+/// use fader::{KeyframeJob, read_keyframes};
+///
+/// let keyframes = read_keyframes("fade.toml")?;
+/// KeyframeJob::open("background.png", keyframes)?
+///     .framerate(30)
+///     .write_video("background.mp4")?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyframeJob {
+    images: Vec<RgbaImage>,
+    keyframes: Vec<Keyframe>,
+    /// Index into `images` of the image shown at each keyframe.
+    keyframe_images: Vec<usize>,
+    framerate: u32,
+    colorspace: ColorSpace,
+    dither: Dither,
+    scaling: Option<Scaling>,
+    encoder: EncoderSettings,
+    jobs: usize,
+}
+
+impl KeyframeJob {
+    /// Start a job animating `image` through `keyframes`, at 10 fps.
+    ///
+    /// Fails if the keyframes are out of order or out of range, or if an
+    /// image they show cannot be opened.
+    pub fn new(image: DynamicImage, keyframes: Vec<Keyframe>) -> Result<Self> {
+        validate(&keyframes).map_err(FaderError::InvalidArgument)?;
+
+        let mut images = vec![image.into_rgba8()];
+        let mut paths: Vec<&Path> = Vec::new();
+        let mut keyframe_images = Vec::with_capacity(keyframes.len());
+        for keyframe in &keyframes {
+            let Some(path) = keyframe.image.as_deref() else {
+                keyframe_images.push(0);
+                continue;
+            };
+            if let Some(index) = paths.iter().position(|&p| p == path) {
+                keyframe_images.push(index + 1);
+                continue;
+            }
+            images.push(open_image(path)?.into_rgba8());
+            paths.push(path);
+            keyframe_images.push(images.len() - 1);
+        }
+
+        Ok(Self {
+            images,
+            keyframes,
+            keyframe_images,
+            framerate: 10,
+            colorspace: ColorSpace::default(),
+            dither: Dither::default(),
+            scaling: None,
+            encoder: EncoderSettings::default(),
+            jobs: 0,
+        })
+    }
+
+    /// Decode the main image at `path` and start a job for it.
+    pub fn open(path: impl AsRef<Path>, keyframes: Vec<Keyframe>) -> Result<Self> {
+        Self::new(open_image(path.as_ref())?, keyframes)
+    }
+
+    /// Frame rate of the output video.
+    pub fn framerate(mut self, framerate: u32) -> Self {
+        self.framerate = framerate;
+        self
+    }
+
+    /// Color space in which pixels are interpolated.
+    pub fn colorspace(mut self, colorspace: ColorSpace) -> Self {
+        self.colorspace = colorspace;
+        self
+    }
+
+    /// Sets the [`Dither`] mode.
+    pub fn dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    /// Scale every image to a fixed output size.
+    pub fn scaling(mut self, scaling: Scaling) -> Self {
+        self.scaling = Some(scaling);
+        self
+    }
+
+    /// The keyframes being animated.
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    /// Whether any keyframe makes the image transparent, which needs an
+    /// output format that [supports alpha](crate::VideoFormat::supports_alpha).
+    pub fn uses_alpha(&self) -> bool {
+        self.keyframes.iter().any(|keyframe| keyframe.opacity < 1.0)
+    }
+
+    /// The step showing keyframe `index` unchanged.
+    fn hold(&self, index: usize) -> Step {
+        let keyframe = &self.keyframes[index];
+        Step {
+            from: self.keyframe_images[index],
+            to: self.keyframe_images[index],
+            blend: 0.0,
+            brightness: keyframe.brightness,
+            opacity: keyframe.opacity,
+            color: keyframe.color,
+        }
+    }
+
+    /// Render frame `index` like [`render`](Renderer::render) when it blends
+    /// images, fades to a color or fades out in more than one way at once.
+    fn render_chained(
+        &self,
+        images: &[Cow<RgbaImage>],
+        index: usize,
+        step: &Step,
+        frame: &mut impl FrameBuffer,
+    ) {
+        let (from, to) = (&images[step.from], &images[step.to]);
+        match_dimensions(from, frame);
+        let thresholds = self.dither.thresholds(index);
+        let color = step.color.channels().map(f32::from);
+        let lerp = |a: u8, b: u8| a as f32 + (b as f32 - a as f32) * step.blend;
+
+        for ((a, out, threshold), b) in pixels(from, frame, &thresholds).zip(to.chunks_exact(4)) {
+            let mut rgb = self
+                .colorspace
+                .mix([a[0], a[1], a[2]], [b[0], b[1], b[2]], step.blend);
+            if step.brightness < 1.0 {
+                rgb = self.colorspace.mix_values(color, rgb, step.brightness);
+            }
+            let alpha = lerp(a[3], b[3]) * step.opacity;
+            for (out, value) in out.iter_mut().zip(rgb.into_iter().chain([alpha])) {
+                *out = Quantize::quantize(value, threshold);
+            }
+        }
+    }
+
+    /// Render the frames of the timeline lazily, in order.
+    pub fn frames(&self) -> Result<impl Iterator<Item = RgbaImage> + '_> {
+        Renderer::frames(self)
+    }
+
+    /// Number of frames of the timeline.
+    pub fn frame_count(&self) -> Result<usize> {
+        Renderer::frame_count(self)
+    }
+
+    /// Options for the encoder used by [`write_video`](Self::write_video).
+    pub fn encoder(mut self, encoder: EncoderSettings) -> Self {
+        self.encoder = encoder;
+        self
+    }
+
+    /// Number of threads rendering frames. `0`, the default, uses every CPU core.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Render every frame and encode them into a video at `output_path`.
+    ///
+    /// The container and codec follow the extension of `output_path`, see
+    /// [`VideoFormat`](crate::VideoFormat).
+    pub fn write_video(&self, output_path: impl AsRef<Path>) -> Result<()> {
+        Renderer::write_video(self, output_path.as_ref())
+    }
+
+    /// Render every frame into `sink` and finalize it.
+    pub fn write_to(&self, sink: &mut dyn FrameSink) -> Result<()> {
+        Renderer::write_to(self, sink)
+    }
+}
+
+impl Renderer for KeyframeJob {
+    type Step = Step;
+    /// The images, scaled to the output size.
+    type Scene<'a> = Vec<Cow<'a, RgbaImage>>;
+
+    fn framerate(&self) -> u32 {
+        self.framerate
+    }
+
+    fn encoder_settings(&self) -> &EncoderSettings {
+        &self.encoder
+    }
+
+    fn thread_count(&self) -> usize {
+        self.jobs
+    }
+
+    /// How every frame is rendered. Frame `i` shows the timeline at
+    /// `i / framerate` seconds, so keyframes keep their exact times, and the
+    /// video runs until the last frame at or before the last keyframe.
+    fn steps(&self) -> Result<Vec<Step>> {
+        timeline::check_framerate(self.framerate)?;
+        let framerate = self.framerate as f32;
+        let duration = self.keyframes[self.keyframes.len() - 1].time;
+        // Tolerate rounding, so that a keyframe at 0.7 s still lands on frame 7.
+        let frame_count = timeline::frames(
+            "keyframe timeline",
+            (duration * framerate + 1e-3).floor() + 1.0,
+        )?;
+
+        let steps = (0..frame_count)
+            .map(|i| {
+                let time = i as f32 / framerate;
+                let next = self.keyframes.partition_point(|k| k.time <= time);
+                if next == 0 || next == self.keyframes.len() {
+                    let index = next.min(self.keyframes.len() - 1);
+                    return self.hold(index);
+                }
+
+                let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+                let t = b.easing.apply((time - a.time) / (b.time - a.time));
+                let lerp = |from: f32, to: f32| from + (to - from) * t;
+                let [r, g, bl] = std::array::from_fn(|c| {
+                    lerp(a.color.channels()[c] as f32, b.color.channels()[c] as f32).round() as u8
+                });
+                Step {
+                    from: self.keyframe_images[next - 1],
+                    to: self.keyframe_images[next],
+                    blend: t,
+                    brightness: lerp(a.brightness, b.brightness),
+                    opacity: lerp(a.opacity, b.opacity),
+                    color: Color::new(r, g, bl),
+                }
+            })
+            .collect();
+        Ok(steps)
+    }
+
+    fn scene(&self) -> Self::Scene<'_> {
+        scale::uniform(&self.images, self.scaling.as_ref())
+    }
+
+    /// Render frame `index`, for one step over the scaled `images`, into `frame`.
+    fn render(
+        &self,
+        images: &Self::Scene<'_>,
+        index: usize,
+        step: &Step,
+        frame: &mut impl FrameBuffer,
+    ) {
+        let blends = step.from != step.to && step.blend > 0.0;
+        let darkens = step.brightness < 1.0;
+        let fades = step.opacity < 1.0;
+        match (blends, darkens, fades) {
+            (false, false, false) => copy_into(&images[step.from], frame),
+            (true, false, false) => blend_images_into(
+                &images[step.from],
+                &images[step.to],
+                step.blend,
+                self.colorspace,
+                self.dither,
+                index,
+                frame,
+            ),
+            (false, true, false) => fade_image_into(
+                &images[step.from],
+                step.brightness,
+                step.color,
+                self.colorspace,
+                self.dither,
+                index,
+                frame,
+            ),
+            (false, false, true) => {
+                fade_opacity_into(&images[step.from], step.opacity, self.dither, index, frame)
+            }
+            // Render chained changes in one pass, quantizing the frame just once.
+            _ => self.render_chained(images, index, step, frame),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toml(text: &str) -> Result<Vec<Keyframe>, String> {
+        let keyframes = decode(parse::toml(text)?, Path::new("timelines"))?;
+        validate(&keyframes)?;
+        Ok(keyframes)
+    }
+
+    fn json(text: &str) -> Result<Vec<Keyframe>, String> {
+        let keyframes = decode(parse::json(text)?, Path::new("timelines"))?;
+        validate(&keyframes)?;
+        Ok(keyframes)
+    }
+
+    #[test]
+    fn readme_example() {
+        let keyframes = toml(
+            r#"
+            color = "black"
+            easing = "ease-in-out"
+
+            [[keyframes]]
+            time = 0
+
+            [[keyframes]]
+            time = 5
+
+            [[keyframes]]
+            time = 7
+            brightness = 0
+
+            [[keyframes]]
+            time = 8
+            image = "night.png"
+
+            [[keyframes]]
+            time = 10
+            brightness = 1
+            "#,
+        )
+        .unwrap();
+
+        let times: Vec<f32> = keyframes.iter().map(|k| k.time).collect();
+        assert_eq!(times, [0.0, 5.0, 7.0, 8.0, 10.0]);
+        let brightness: Vec<f32> = keyframes.iter().map(|k| k.brightness).collect();
+        assert_eq!(brightness, [1.0, 1.0, 0.0, 0.0, 1.0]);
+        assert!(keyframes.iter().all(|k| k.easing == Easing::EaseInOut));
+        assert!(keyframes.iter().all(|k| k.color == Color::BLACK));
+        let night = Some(Path::new("timelines").join("night.png"));
+        assert_eq!(keyframes[2].image, None);
+        assert_eq!(keyframes[3].image, night);
+        assert_eq!(keyframes[4].image, night);
+    }
+
+    #[test]
+    fn readme_json_example() {
+        let keyframes =
+            json(r#"{"keyframes": [{"time": 0}, {"time": 5}, {"time": 7, "brightness": 0}]}"#)
+                .unwrap();
+        let brightness: Vec<f32> = keyframes.iter().map(|k| k.brightness).collect();
+        assert_eq!(brightness, [1.0, 1.0, 0.0]);
+        assert!(keyframes.iter().all(|k| k.easing == Easing::Linear));
+    }
+
+    #[test]
+    fn toml_inline_tables() {
+        let keyframes = toml(
+            "keyframes = [ { time = 0 }, { time = 1, brightness = 0 } ]\n\
+             color = \"#ff8000\"",
+        )
+        .unwrap();
+        assert_eq!(keyframes.len(), 2);
+        assert_eq!(keyframes[1].brightness, 0.0);
+        assert_eq!(keyframes[1].color, Color::new(255, 128, 0));
+    }
+
+    #[test]
+    fn json_null_carries_settings_over() {
+        let keyframes = json(
+            r#"{"keyframes": [
+                {"time": 0, "opacity": 0.5, "color": "white", "easing": "sine"},
+                {"time": 1, "opacity": null, "color": null, "easing": null}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(keyframes[1].opacity, 0.5);
+        assert_eq!(keyframes[1].color, Color::WHITE);
+        // Easing is the only setting that does not carry over.
+        assert_eq!(keyframes[1].easing, Easing::Linear);
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        assert!(toml("[[keyframes]]\nbrightness = 0").is_err());
+        assert!(toml("[[keyframes]]\ntime = 0\nbogus = 1").is_err());
+        assert!(toml("keyframes = []").is_err());
+        assert!(toml("[[keyframes]]\ntime = 0\ncolor = \"nope\"").is_err());
+        assert!(json(r#"{"keyframes": [{"time": 2}, {"time": 1}]}"#).is_err());
+        assert!(json(r#"{"keyframes": [{"time": 0, "brightness": 2}]}"#).is_err());
+        assert!(json(r#"{"keyframes": [{"time": "0"}]}"#).is_err());
+        assert!(json(r#"{"frames": []}"#).is_err());
+        assert!(json(r#"{"keyframes": [{"time": 0}, {"time": 1e9}]}"#).is_err());
+        assert!(toml("[[keyframes]]\ntime = 3e7").is_err());
+    }
+
+    #[test]
+    fn rejects_timelines_with_too_many_frames() {
+        let job = KeyframeJob::new(
+            DynamicImage::new_rgba8(1, 1),
+            vec![Keyframe::new(0.0), Keyframe::new(5e5)],
+        )
+        .unwrap();
+        assert!(job.clone().framerate(1).steps().is_ok());
+        assert!(job.framerate(30).steps().is_err());
+    }
+
+    fn brightness(keyframes: Vec<Keyframe>, framerate: u32) -> Vec<f32> {
+        KeyframeJob::new(DynamicImage::new_rgba8(1, 1), keyframes)
+            .unwrap()
+            .framerate(framerate)
+            .steps()
+            .unwrap()
+            .iter()
+            .map(|step| step.brightness)
+            .collect()
+    }
+
+    #[test]
+    fn frames_sample_keyframes_at_their_times() {
+        let keyframes = vec![
+            Keyframe::new(0.0),
+            Keyframe {
+                brightness: 0.0,
+                ..Keyframe::new(1.0)
+            },
+        ];
+        let factors = brightness(keyframes, 10);
+        assert_eq!(factors.len(), 11);
+        for (i, factor) in factors.iter().enumerate() {
+            assert!(
+                (factor - (1.0 - i as f32 / 10.0)).abs() < 1e-6,
+                "{factors:?}"
+            );
+        }
+        assert_eq!(factors[10], 0.0);
+    }
+
+    #[test]
+    fn keyframes_between_frames_and_rounded_times() {
+        let keyframes = vec![
+            Keyframe::new(0.0),
+            Keyframe {
+                brightness: 0.0,
+                ..Keyframe::new(0.7)
+            },
+        ];
+        assert_eq!(brightness(keyframes.clone(), 10).len(), 8);
+        assert_eq!(brightness(keyframes.clone(), 10)[7], 0.0);
+        // At 4 fps the keyframe falls between frames 2 and 3, at 0.5 and 0.75 s.
+        assert_eq!(brightness(keyframes, 4).len(), 3);
+        assert_eq!(brightness(vec![Keyframe::new(0.0)], 10), [1.0]);
+    }
+}
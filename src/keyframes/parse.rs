@@ -0,0 +1,38 @@
+//! The JSON and TOML documents describing a timeline.
+//!
+//! Both formats deserialize into the same [`Document`], whose optional fields
+//! are left out or `null` where a keyframe carries a setting over.
+
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// A timeline file as written, before settings are carried over.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Document {
+    pub(crate) color: Option<String>,
+    pub(crate) easing: Option<String>,
+    pub(crate) keyframes: Vec<Entry>,
+}
+
+/// One keyframe of a [`Document`].
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Entry {
+    pub(crate) time: f32,
+    pub(crate) brightness: Option<f32>,
+    pub(crate) opacity: Option<f32>,
+    pub(crate) easing: Option<String>,
+    pub(crate) color: Option<String>,
+    pub(crate) image: Option<PathBuf>,
+}
+
+/// Parse a JSON document.
+pub(crate) fn json(text: &str) -> Result<Document, String> {
+    serde_json::from_str(text).map_err(|e| e.to_string())
+}
+
+/// Parse a TOML document.
+pub(crate) fn toml(text: &str) -> Result<Document, String> {
+    toml::from_str(text).map_err(|e| e.to_string().trim_end().to_owned())
+}
@@ -0,0 +1,63 @@
+//! Create a video from an image that fades in a chosen style.
+//!
+//! The [`FadeJob`] builder describes a fade of a single image, [`Crossfade`]
+//! dissolves a sequence of images into each other and [`KeyframeJob`] animates
+//! arbitrary [keyframes](Keyframe). All of them can either yield the rendered
+//! frames or encode them into a video through a [`FrameSink`].
+//!
+//! # Features
+//!
+//! * `ffmpeg` (default): encode through an external `ffmpeg` binary.
+//! * `animation` (default): encode animated GIF and PNG in-process.
+//! * `av1`: encode AV1 into `.ivf` files in-process, without `ffmpeg`.
+
+mod color;
+mod colorspace;
+mod crossfade;
+mod dissolve;
+mod dither;
+mod easing;
+mod effect;
+mod encode;
+mod error;
+mod inputs;
+mod job;
+mod keyframes;
+mod matte;
+mod noise;
+mod render;
+mod scale;
+mod style;
+mod timeline;
+mod wipe;
+
+pub use color::Color;
+pub use colorspace::ColorSpace;
+pub use crossfade::Crossfade;
+pub use dissolve::{Dissolve, Noise};
+pub use dither::Dither;
+pub use easing::Easing;
+pub use effect::{ColorFade, Contrast, Desaturate, Effect, Exposure, Sepia, Temperature};
+#[cfg(feature = "av1")]
+pub use encode::Av1Sink;
+#[cfg(feature = "ffmpeg")]
+pub use encode::FfmpegSink;
+#[cfg(feature = "animation")]
+pub use encode::{ApngSink, GifSink};
+pub use encode::{
+    BitDepth, EncoderSettings, FfmpegInput, FrameFormat, FramePattern, FrameSink, GifDither,
+    ImageSequenceSink, Rgba16Image, VideoCodec, VideoFormat, open_sink,
+};
+pub use error::{FaderError, Result};
+pub use inputs::expand_inputs;
+pub use job::FadeJob;
+pub use keyframes::{Keyframe, KeyframeJob, read_keyframes};
+pub use matte::LumaMatte;
+pub use render::{
+    FrameBuffer, apply_effect, apply_effect_into, blend_images, blend_images_into, fade_image,
+    fade_image_into, fade_opacity, fade_opacity_into,
+};
+pub use scale::{Fit, Resampling, Scaling, Size};
+pub use style::{FadeStyle, cyclic_fade_factors, fade_factors};
+pub use timeline::{FadeTimeline, Repeat};
+pub use wipe::{Wipe, WipeShape};
@@ -1,125 +1,834 @@
 use clap::{Parser, ValueEnum};
-use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
-use std::{path::PathBuf, process::Command};
-use tempfile::tempdir;
-
-#[derive(Debug, Clone, ValueEnum)]
-enum FadeStyle {
-    ToDark,
-    FromDark,
-    ToDarkAndBack,
-    FromDarkAndBack,
-}
+use fader::{
+    Color, ColorSpace, Contrast, Crossfade, Desaturate, Dissolve, Dither, Easing, EncoderSettings,
+    Exposure, FadeJob, FadeStyle, FaderError, FfmpegInput, Fit, FrameFormat, FramePattern,
+    FrameSink, GifDither, ImageSequenceSink, Keyframe, KeyframeJob, LumaMatte, Noise, Repeat,
+    Resampling, Scaling, Sepia, Size, Temperature, VideoCodec, VideoFormat, Wipe, WipeShape,
+    expand_inputs, open_sink, read_keyframes,
+};
+use rayon::{ThreadPoolBuilder, prelude::*};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
 #[derive(Parser, Debug)]
 #[command(name = "ImageFader")]
 struct Args {
-    /// Input image path
-    #[arg(value_name = "INPUT")]
-    input: PathBuf,
+    /// Input images, directories of images or quoted glob patterns such as
+    /// "photos/*.jpg". Several inputs are faded into one video each, unless
+    /// --crossfade dissolves them into a single one
+    #[arg(value_name = "INPUT", required = true)]
+    inputs: Vec<PathBuf>,
 
-    /// Output video path. Defaults to <input>.mp4
+    /// Output video path. The extension selects the format: .mp4, .webm, .mov,
+    /// .ivf, or the animated images .gif, .png/.apng and .webp. Defaults to
+    /// <input>.mp4, or <input>.webm when fading to transparency
     #[arg(short, long)]
     output: Option<PathBuf>,
 
+    /// Directory the videos are saved in when --output is not given
+    #[arg(long, conflicts_with = "output")]
+    output_dir: Option<PathBuf>,
+
+    /// File name of each video when --output is not given. {stem} and {ext}
+    /// are replaced by the input's file stem and extension, {style} by the
+    /// fade style and {index} by the input's position, counting from 1
+    #[arg(long, conflicts_with = "output", value_parser = parse_name_template)]
+    name_template: Option<String>,
+
+    /// Save the rendered frames as numbered images in this directory. No video is
+    /// encoded unless --output, --output-dir or --name-template asks for one
+    /// too. The frames of several inputs go into a subdirectory for each,
+    /// named after its file stem
+    #[arg(long, value_name = "DIR")]
+    frames_dir: Option<PathBuf>,
+
+    /// File name of the saved frames without extension, where {frame} is the
+    /// frame number counting from 0 and {frame:N} the number padded to N digits
+    #[arg(long, default_value = "frame_{frame:04}", requires = "frames_dir")]
+    frame_pattern: FramePattern,
+
+    /// Image format of the saved frames: png, png16 for 16-bit PNG, jpeg, webp
+    /// or tiff
+    #[arg(long, value_enum, default_value = "png", requires = "frames_dir")]
+    frame_format: FrameFormat,
+
+    /// Quality of frames saved as JPEG, from 1 to 100
+    #[arg(long, default_value = "90", requires = "frames_dir",
+          value_parser = clap::value_parser!(u8).range(1..=100))]
+    jpeg_quality: u8,
+
+    /// Size of the output video as WIDTHxHEIGHT, such as 1920x1080. Defaults to
+    /// the size of the first input
+    #[arg(long)]
+    size: Option<Size>,
+
+    /// How images of another aspect ratio are fitted into --size: scaled to
+    /// fit inside, to cover it with the rest cropped, stretched, or fitted
+    /// inside and padded with --pad-color
+    #[arg(long, value_enum, default_value = "pad", requires = "size")]
+    fit: Fit,
+
+    /// Color around images fitted with --fit pad
+    #[arg(long, default_value = "black", requires = "size")]
+    pad_color: Color,
+
+    /// Filter resampling images scaled to --size
+    #[arg(long, value_enum, default_value = "lanczos", requires = "size")]
+    resampling: Resampling,
+
     /// Frame rate of the output video
-    #[arg(short, long, default_value = "10")]
+    #[arg(short, long, default_value = "10", value_parser = clap::value_parser!(u32).range(1..))]
     framerate: u32,
 
-    /// Duration of the fade effect in seconds
-    #[arg(short, long, default_value = "2")]
+    /// Duration of the fade effect, or of each crossfade transition, in seconds
+    #[arg(short, long, default_value = "2", allow_negative_numbers = true,
+          value_parser = parse_seconds)]
     duration: f32,
 
+    /// Time in seconds the image stays static before the fade
+    #[arg(long, default_value = "0", conflicts_with = "crossfade", allow_negative_numbers = true,
+          value_parser = parse_seconds)]
+    hold_start: f32,
+
+    /// Time in seconds the to-dark-and-back and from-dark-and-back styles
+    /// pause at their turnaround
+    #[arg(long, default_value = "0", conflicts_with = "crossfade", allow_negative_numbers = true,
+          value_parser = parse_seconds)]
+    hold_middle: f32,
+
+    /// Time in seconds the last frame stays static after the fade
+    #[arg(long, default_value = "0", conflicts_with = "crossfade", allow_negative_numbers = true,
+          value_parser = parse_seconds)]
+    hold_end: f32,
+
+    /// Loop the fade seamlessly, never showing the same frame twice where the
+    /// clip wraps around
+    #[arg(long = "loop", conflicts_with = "crossfade")]
+    looping: bool,
+
+    /// Number of times the looping fade plays in the output
+    #[arg(long, requires = "looping", value_parser = clap::value_parser!(u32).range(1..))]
+    repeat: Option<u32>,
+
+    /// Repeat the looping fade to last about this many seconds, rounded to
+    /// whole cycles
+    #[arg(long, requires = "looping", conflicts_with = "repeat", allow_negative_numbers = true,
+          value_parser = parse_seconds)]
+    loop_duration: Option<f32>,
+
     /// Style of the fade effect
-    #[arg(short, long, value_enum, default_value = "to-dark")]
+    #[arg(
+        short,
+        long,
+        value_enum,
+        default_value = "to-dark",
+        conflicts_with = "crossfade"
+    )]
     style: FadeStyle,
+
+    /// Sweep the fade across the frame: a linear edge, a radial iris or a clock
+    /// hand. The style decides whether the wipe covers or uncovers the image
+    #[arg(long, value_enum, conflicts_with_all = ["crossfade", "timeline"])]
+    wipe: Option<WipeShape>,
+
+    /// Angle of the wipe in degrees, clockwise: the direction a linear wipe
+    /// moves in, 0 being left to right, or where a clock wipe starts, 0 being
+    /// 12 o'clock
+    #[arg(
+        long,
+        default_value = "0",
+        requires = "wipe",
+        allow_negative_numbers = true,
+        value_parser = parse_finite
+    )]
+    wipe_angle: f32,
+
+    /// Center of radial and clock wipes as X,Y fractions of the frame, 0,0
+    /// being the top left corner
+    #[arg(long, default_value = "0.5,0.5", requires = "wipe", value_parser = parse_center)]
+    wipe_center: (f32, f32),
+
+    /// Width of the blended edge of the wipe, as a fraction of the way it
+    /// travels from 0 (hard) to 1
+    #[arg(long, default_value = "0", requires = "wipe", value_parser = parse_fraction)]
+    wipe_softness: f32,
+
+    /// Grayscale image deciding when each pixel fades: dark areas of the mask
+    /// fade first and bright ones last. The style decides the direction
+    #[arg(long, value_name = "MASK", conflicts_with_all = ["crossfade", "timeline", "wipe"])]
+    matte: Option<PathBuf>,
+
+    /// Range of the mask's luminance blended at once, from 0 (hard) to 1
+    #[arg(long, default_value = "0.1", requires = "matte", value_parser = parse_fraction)]
+    matte_softness: f32,
+
+    /// Dissolve the image grain by grain in the order of white noise, evenly
+    /// spread blue noise or cloudy Perlin noise. The style decides the direction
+    #[arg(long, value_enum, conflicts_with_all = ["crossfade", "timeline", "wipe", "matte"])]
+    dissolve: Option<Noise>,
+
+    /// Seed of the dissolve noise, the same seed dissolving the same pixels first
+    #[arg(long, default_value = "0", requires = "dissolve")]
+    seed: u64,
+
+    /// Size in pixels of the grains dissolving together, or of the largest
+    /// blobs of Perlin noise
+    #[arg(long, default_value = "1", requires = "dissolve",
+          value_parser = clap::value_parser!(u32).range(1..))]
+    grain: u32,
+
+    /// Fraction of the fade over which each grain blends, from 0 (abrupt) to 1
+    #[arg(long, default_value = "0", requires = "dissolve", value_parser = parse_fraction)]
+    dissolve_softness: f32,
+
+    /// Easing curve of the fade: linear, ease-in, ease-out, ease-in-out, cubic,
+    /// sine, exponential, smoothstep or cubic-bezier(x1,y1,x2,y2). Timeline files
+    /// set their own with the `easing` key
+    #[arg(short, long, default_value = "linear")]
+    easing: Easing,
+
+    /// Color to fade to or from, as #rrggbb, #rgb or a color name
+    #[arg(short, long, default_value = "black", conflicts_with = "crossfade")]
+    color: Color,
+
+    /// Color space the fade is computed in: naive sRGB values, linear light, or
+    /// perceptually even OKLab
+    #[arg(long, value_enum, default_value = "naive")]
+    colorspace: ColorSpace,
+
+    /// Dithering against banding in slow fades: none (round), ordered, blue-noise,
+    /// or temporal blue noise that changes every frame
+    #[arg(long, value_enum, default_value = "none")]
+    dither: Dither,
+
+    /// Fade the alpha channel to transparency instead of fading to a color
+    #[arg(long, conflicts_with_all = ["color", "crossfade"])]
+    fade_alpha: bool,
+
+    /// Fade to a color grade instead of a color: desaturate to grayscale, sepia,
+    /// contrast down to flat gray, exposure by --stops or the color temperature
+    /// of --kelvin
+    #[arg(long, value_enum, conflicts_with_all = ["color", "fade_alpha", "crossfade", "timeline"])]
+    effect: Option<EffectName>,
+
+    /// Change of exposure the exposure effect reaches, in photographic stops,
+    /// negative to darken
+    #[arg(
+        long,
+        default_value = "-3",
+        requires = "effect",
+        allow_negative_numbers = true,
+        value_parser = parse_exposure
+    )]
+    stops: Exposure,
+
+    /// Color temperature the temperature effect reaches, in kelvin: below 6500
+    /// (daylight) for warmer light, above for colder
+    #[arg(long, default_value = "3000", requires = "effect", value_parser = parse_temperature)]
+    kelvin: Temperature,
+
+    /// Animate the image through the keyframes in a .toml or .json file
+    /// instead of a fade style
+    #[arg(long, value_name = "FILE", conflicts_with_all = [
+        "crossfade", "duration", "style", "easing", "color", "fade_alpha", "looping",
+        "hold_start", "hold_middle", "hold_end",
+    ])]
+    timeline: Option<PathBuf>,
+
+    /// Dissolve the input images into each other instead of fading a single image
+    #[arg(long)]
+    crossfade: bool,
+
+    /// Time in seconds each image is held between crossfade transitions
+    #[arg(long, default_value = "0", requires = "crossfade", allow_negative_numbers = true,
+          value_parser = parse_seconds)]
+    hold: f32,
+
+    /// How frames are handed to ffmpeg: streamed over stdin, or saved as PNG
+    /// files first for debugging
+    #[arg(long, value_enum, default_value = "pipe")]
+    ffmpeg_input: FfmpegInput,
+
+    /// Video codec for .mp4, .webm, .mov and .ivf output. Defaults to h264 for
+    /// .mp4, vp9 for .webm, prores for .mov and av1 for .ivf
+    #[arg(long, value_enum)]
+    codec: Option<VideoCodec>,
+
+    /// Constant rate factor of the codec, lower meaning better quality and
+    /// bigger files: up to 51 for h264 and h265, 63 for vp9 and av1
+    #[arg(long)]
+    crf: Option<u8>,
+
+    /// Target bitrate such as 8M or 2500k
+    #[arg(long)]
+    bitrate: Option<String>,
+
+    /// Encoder speed preset: ultrafast to veryslow for h264 and h265, 0 (slowest)
+    /// to 8 for vp9 and av1
+    #[arg(long)]
+    preset: Option<String>,
+
+    /// ffmpeg pixel format, such as yuv420p10le for 10-bit video
+    #[arg(long)]
+    pixel_format: Option<String>,
+
+    /// Extra argument passed to ffmpeg before the output path. Repeat for
+    /// several, as in --ffmpeg-arg=-tune --ffmpeg-arg=grain
+    #[arg(long = "ffmpeg-arg", value_name = "ARG", allow_hyphen_values = true)]
+    ffmpeg_args: Vec<String>,
+
+    /// Number of threads rendering frames, or fading inputs side by side when
+    /// there are several, 0 using every CPU core
+    #[arg(short, long, default_value = "0")]
+    jobs: usize,
+
+    /// Number of times GIF, APNG and WebP animations play, 0 looping forever
+    #[arg(long, default_value = "0")]
+    loop_count: u16,
+
+    /// Number of palette colors in each GIF frame
+    #[arg(long, default_value = "256", value_parser = clap::value_parser!(u16).range(2..=256))]
+    gif_colors: u16,
+
+    /// Dithering used when reducing GIF frames to their palette
+    #[arg(long, value_enum, default_value = "floyd-steinberg")]
+    gif_dither: GifDither,
+}
+
+/// Built-in effects that --effect selects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum EffectName {
+    Desaturate,
+    Sepia,
+    Contrast,
+    Exposure,
+    Temperature,
+}
+
+/// Placeholders that --name-template replaces.
+const NAME_PLACEHOLDERS: [&str; 4] = ["stem", "ext", "style", "index"];
+
+/// Check that a --name-template only uses known placeholders.
+fn parse_name_template(value: &str) -> Result<String, String> {
+    let mut rest = value;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            return Err("unclosed `{`".into());
+        };
+        let name = &rest[start + 1..start + end];
+        if !NAME_PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "unknown placeholder {{{name}}}, expected one of {{{}}}",
+                NAME_PLACEHOLDERS.join("}, {")
+            ));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(value.to_owned())
+}
+
+/// Parse an X,Y pair of fractions of the frame, such as `0.5,0.5`.
+fn parse_center(value: &str) -> Result<(f32, f32), String> {
+    let (x, y) = value
+        .split_once(',')
+        .ok_or_else(|| format!("expected X,Y, got `{value}`"))?;
+    let coordinate = |text: &str| match text.trim().parse::<f32>() {
+        Ok(n) if n.is_finite() => Ok(n),
+        _ => Err(format!("invalid coordinate `{text}` in `{value}`")),
+    };
+    Ok((coordinate(x)?, coordinate(y)?))
+}
+
+/// Parse a finite number.
+fn parse_finite(value: &str) -> Result<f32, String> {
+    let number: f32 = value.parse().map_err(|e| format!("{e}"))?;
+    if !number.is_finite() {
+        return Err("must be a finite number".into());
+    }
+    Ok(number)
 }
 
-fn main() {
+/// Parse a change of exposure in stops.
+fn parse_exposure(value: &str) -> Result<Exposure, String> {
+    Exposure::new(parse_finite(value)?).map_err(invalid_value)
+}
+
+/// Parse a color temperature in kelvin.
+fn parse_temperature(value: &str) -> Result<Temperature, String> {
+    Temperature::new(parse_finite(value)?).map_err(invalid_value)
+}
+
+/// Message of a library error for an argument clap reports as invalid.
+fn invalid_value(error: FaderError) -> String {
+    match error {
+        FaderError::InvalidArgument(message) => message,
+        error => error.to_string(),
+    }
+}
+
+/// Parse a number from 0 to 1.
+fn parse_fraction(value: &str) -> Result<f32, String> {
+    let fraction: f32 = value.parse().map_err(|e| format!("{e}"))?;
+    if !(0.0..=1.0).contains(&fraction) {
+        return Err("must be a number from 0 to 1".into());
+    }
+    Ok(fraction)
+}
+
+/// Parse a non-negative, finite number of seconds.
+fn parse_seconds(value: &str) -> Result<f32, String> {
+    let seconds: f32 = value.parse().map_err(|e| format!("{e}"))?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err("must be a non-negative number of seconds".into());
+    }
+    Ok(seconds)
+}
+
+fn main() -> ExitCode {
     let args = Args::parse();
 
-    let output_path = args.output.unwrap_or_else(|| {
-        let stem = args.input.file_stem().unwrap_or_default();
-        let mut output = PathBuf::from(stem);
-        output.set_extension("mp4");
-        output
+    match run(&args) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// Render the videos described by `args`.
+///
+/// When several inputs are faded, a failure only stops its own video. The
+/// returned exit code is then that of the first failed input.
+fn run(args: &Args) -> Result<ExitCode, FaderError> {
+    if args.hold_middle > 0.0 && !args.style.turns_around() {
+        return Err(FaderError::InvalidArgument(
+            "--hold-middle needs a style that fades there and back".into(),
+        ));
+    }
+
+    let inputs = expand_inputs(&args.inputs)?;
+    if inputs.len() > 1 && !args.crossfade && args.output.is_some() {
+        return Err(FaderError::InvalidArgument(
+            "--output names a single video, use --output-dir and --name-template \
+             to name the videos of several inputs"
+                .into(),
+        ));
+    }
+
+    let keyframes = args.timeline.as_ref().map(read_keyframes).transpose()?;
+    let luma_matte = match &args.matte {
+        Some(mask) => Some(LumaMatte::open(mask)?.softness(args.matte_softness)),
+        None => None,
+    };
+    let fades_alpha = match &keyframes {
+        Some(keyframes) => keyframes.iter().any(|keyframe| keyframe.opacity < 1.0),
+        None => args.fade_alpha,
+    };
+
+    let writes_video = args.frames_dir.is_none()
+        || args.output.is_some()
+        || args.output_dir.is_some()
+        || args.name_template.is_some();
+    let several = inputs.len() > 1 && !args.crossfade;
+    let outputs: Vec<Output> = inputs
+        .iter()
+        .take(if args.crossfade { 1 } else { inputs.len() })
+        .enumerate()
+        .map(|(index, input)| Output {
+            video: writes_video.then(|| output_path(args, input, index, fades_alpha)),
+            frames_dir: args.frames_dir.as_ref().map(|dir| {
+                if several {
+                    dir.join(input.file_stem().unwrap_or_default())
+                } else {
+                    dir.clone()
+                }
+            }),
+        })
+        .collect();
+    let encoder = EncoderSettings {
+        ffmpeg_input: args.ffmpeg_input,
+        loop_count: args.loop_count,
+        gif_colors: args.gif_colors,
+        gif_dither: args.gif_dither,
+        codec: args.codec,
+        crf: args.crf,
+        bitrate: args.bitrate.clone(),
+        preset: args.preset.clone(),
+        pixel_format: args.pixel_format.clone(),
+        ffmpeg_args: args.ffmpeg_args.clone(),
+    };
+
+    if let Some(video) = &outputs[0].video
+        && fades_alpha
+        && !encoder.keeps_alpha(VideoFormat::from_path(video))
+    {
+        return Err(FaderError::InvalidArgument(
+            "fading to transparency needs an output format with an alpha channel: \
+             .webm, .mov, .png, .apng or .webp, with a codec and pixel format keeping it"
+                .into(),
+        ));
+    }
+    if args.frames_dir.is_some() && fades_alpha && !args.frame_format.supports_alpha() {
+        return Err(FaderError::InvalidArgument(
+            "fading to transparency needs a --frame-format with an alpha channel".into(),
+        ));
+    }
+    check_distinct(
+        &inputs,
+        outputs.iter().map(|output| output.video.as_ref()),
+        "be saved to",
+        "add {index} or {ext} to --name-template",
+    )?;
+    check_distinct(
+        &inputs,
+        outputs.iter().map(|output| output.frames_dir.as_ref()),
+        "save frames to",
+        "rename one of them",
+    )?;
+    if let Some(dir) = args.output_dir.as_ref().filter(|_| writes_video) {
+        fs::create_dir_all(dir)?;
+    }
+
+    if args.crossfade {
+        let mut crossfade = Crossfade::open(&inputs)?
+            .framerate(args.framerate)
+            .transition(args.duration)
+            .hold(args.hold)
+            .easing(args.easing)
+            .colorspace(args.colorspace)
+            .dither(args.dither)
+            .jobs(args.jobs);
+        if let Some(scaling) = scaling(args) {
+            crossfade = crossfade.scaling(scaling);
+        }
+        let frame_count = crossfade.frame_count()?;
+        crossfade.write_to(&mut open_sinks(args, &outputs[0], frame_count, &encoder)?)?;
+        outputs[0].report();
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if inputs.len() == 1 {
+        let keyframes = keyframes.as_deref();
+        fade(
+            args,
+            &inputs[0],
+            &outputs[0],
+            keyframes,
+            luma_matte.as_ref(),
+            &encoder,
+            args.jobs,
+        )?;
+        outputs[0].report();
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // Fade several inputs side by side, each rendering on a single thread.
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(args.jobs)
+        .build()
+        .map_err(|e| FaderError::Io(io::Error::other(e)))?;
+    let results: Vec<Result<(), FaderError>> = pool.install(|| {
+        inputs
+            .par_iter()
+            .zip(&outputs)
+            .map(|(input, output)| {
+                let result = fade(
+                    args,
+                    input,
+                    output,
+                    keyframes.as_deref(),
+                    luma_matte.as_ref(),
+                    &encoder,
+                    1,
+                );
+                match &result {
+                    Ok(()) => output.report(),
+                    Err(e) => eprintln!("error: {}: {e}", input.display()),
+                }
+                result
+            })
+            .collect()
     });
 
-    let img = image::open(&args.input).expect("Failed to open input image");
-
-    let frame_count = (args.duration * args.framerate as f32).ceil() as usize;
-    let fade_factors = match args.style {
-        FadeStyle::ToDark => (0..frame_count)
-            .map(|i| 1.0 - i as f32 / (frame_count - 1) as f32)
-            .collect(),
-        FadeStyle::FromDark => (0..frame_count)
-            .map(|i| i as f32 / (frame_count - 1) as f32)
-            .collect(),
-        FadeStyle::ToDarkAndBack => {
-            let half = frame_count / 2;
-            let down: Vec<f32> = (0..half)
-                .map(|i| 1.0 - i as f32 / (half - 1) as f32)
-                .collect();
-            let up: Vec<f32> = (0..(frame_count - half))
-                .map(|i| i as f32 / (frame_count - half - 1) as f32)
-                .collect();
-            [down, up].concat()
+    let failed: Vec<_> = inputs
+        .iter()
+        .zip(&results)
+        .filter_map(|(input, result)| Some((input, result.as_ref().err()?)))
+        .collect();
+    println!(
+        "{} of {} {} saved, {} failed",
+        inputs.len() - failed.len(),
+        inputs.len(),
+        if writes_video {
+            "videos"
+        } else {
+            "frame sequences"
+        },
+        failed.len()
+    );
+    for (input, _) in &failed {
+        eprintln!("failed: {}", input.display());
+    }
+    Ok(failed
+        .first()
+        .map_or(ExitCode::SUCCESS, |(_, e)| ExitCode::from(e.exit_code())))
+}
+
+/// Where the fade of one input is saved: a video, a directory of frames, or both.
+struct Output {
+    video: Option<PathBuf>,
+    frames_dir: Option<PathBuf>,
+}
+
+impl Output {
+    /// Tell where the fade was saved.
+    fn report(&self) {
+        if let Some(video) = &self.video {
+            println!("Video saved to {}", video.display());
         }
-        FadeStyle::FromDarkAndBack => {
-            let half = frame_count / 2;
-            let up: Vec<f32> = (0..half).map(|i| i as f32 / (half - 1) as f32).collect();
-            let down: Vec<f32> = (0..(frame_count - half))
-                .map(|i| 1.0 - i as f32 / (frame_count - half - 1) as f32)
-                .collect();
-            [up, down].concat()
+        if let Some(dir) = &self.frames_dir {
+            println!("Frames saved to {}", dir.display());
         }
-    };
+    }
+}
 
-    let tmpdir = tempdir().expect("Failed to create temp dir");
-
-    for (i, factor) in fade_factors.iter().enumerate() {
-        let faded = fade_image(&img, *factor);
-        let path = tmpdir.path().join(format!("frame_{:04}.png", i));
-        faded.save(&path).expect("Failed to save frame");
-    }
-
-    let status = Command::new("ffmpeg")
-        .args([
-            "-y",
-            "-framerate",
-            &args.framerate.to_string(),
-            "-i",
-            &tmpdir.path().join("frame_%04d.png").to_string_lossy(),
-            "-c:v",
-            "libx264",
-            "-pix_fmt",
-            "yuv420p",
-            &output_path.to_string_lossy(),
-        ])
-        .status()
-        .expect("Failed to run ffmpeg");
-
-    if !status.success() {
-        eprintln!("FFmpeg failed");
+/// Fail if two `inputs` would write to the same of their `paths`, which would
+/// otherwise overwrite each other.
+fn check_distinct<'a>(
+    inputs: &[PathBuf],
+    paths: impl Iterator<Item = Option<&'a PathBuf>>,
+    verb: &str,
+    hint: &str,
+) -> Result<(), FaderError> {
+    let paths: Vec<_> = paths.collect();
+    for (index, path) in paths.iter().enumerate() {
+        let Some(path) = path else { continue };
+        if let Some(earlier) = paths[..index].iter().position(|p| p == &Some(*path)) {
+            return Err(FaderError::InvalidArgument(format!(
+                "{} and {} would both {verb} {}, {hint}",
+                inputs[earlier].display(),
+                inputs[index].display(),
+                path.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Where the video of the input at `index` is saved.
+fn output_path(args: &Args, input: &Path, index: usize, fades_alpha: bool) -> PathBuf {
+    if let Some(output) = &args.output {
+        return output.clone();
+    }
+    let template = args.name_template.as_deref().unwrap_or(if fades_alpha {
+        "{stem}.webm"
     } else {
-        println!("Video saved to {}", output_path.display());
+        "{stem}.mp4"
+    });
+    let style = match args.timeline {
+        Some(_) => "timeline".to_owned(),
+        None => args
+            .style
+            .to_possible_value()
+            .map(|value| value.get_name().to_owned())
+            .unwrap_or_default(),
+    };
+
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = start + rest[start..].find('}').unwrap_or(rest.len() - start);
+        name.push_str(&rest[..start]);
+        match &rest[start + 1..end] {
+            "stem" => name.push_str(&input.file_stem().unwrap_or_default().to_string_lossy()),
+            "ext" => name.push_str(&input.extension().unwrap_or_default().to_string_lossy()),
+            "style" => name.push_str(&style),
+            "index" => name.push_str(&(index + 1).to_string()),
+            placeholder => unreachable!("{{{placeholder}}} was rejected by parse_name_template"),
+        }
+        rest = &rest[end + 1..];
+    }
+    name.push_str(rest);
+
+    match &args.output_dir {
+        Some(dir) => dir.join(name),
+        None => PathBuf::from(name),
     }
 }
 
-fn fade_image(img: &DynamicImage, alpha: f32) -> DynamicImage {
-    let (width, height) = img.dimensions();
-    let mut output = ImageBuffer::new(width, height);
+/// Fade `input` into `output`, rendering on `jobs` threads.
+fn fade(
+    args: &Args,
+    input: &Path,
+    output: &Output,
+    keyframes: Option<&[Keyframe]>,
+    luma_matte: Option<&LumaMatte>,
+    encoder: &EncoderSettings,
+    jobs: usize,
+) -> Result<(), FaderError> {
+    if let Some(keyframes) = keyframes {
+        let mut job = KeyframeJob::open(input, keyframes.to_vec())?
+            .framerate(args.framerate)
+            .colorspace(args.colorspace)
+            .dither(args.dither)
+            .jobs(jobs);
+        if let Some(scaling) = scaling(args) {
+            job = job.scaling(scaling);
+        }
+        let frame_count = job.frame_count()?;
+        return job.write_to(&mut open_sinks(args, output, frame_count, encoder)?);
+    }
 
-    for (x, y, pixel) in img.to_rgba8().enumerate_pixels() {
-        let [r, g, b, a] = pixel.0;
-        let faded_pixel = Rgba([
-            ((r as f32) * alpha) as u8,
-            ((g as f32) * alpha) as u8,
-            ((b as f32) * alpha) as u8,
-            a,
-        ]);
-        output.put_pixel(x, y, faded_pixel);
+    let mut job = FadeJob::open(input)?
+        .framerate(args.framerate)
+        .duration(args.duration)
+        .hold_start(args.hold_start)
+        .hold_middle(args.hold_middle)
+        .hold_end(args.hold_end)
+        .looping(args.looping)
+        .repeat(match (args.repeat, args.loop_duration) {
+            (_, Some(seconds)) => Repeat::Duration(seconds),
+            (times, None) => Repeat::Times(times.unwrap_or(1)),
+        })
+        .style(args.style)
+        .easing(args.easing)
+        .color(args.color)
+        .colorspace(args.colorspace)
+        .dither(args.dither)
+        .fade_alpha(args.fade_alpha)
+        .jobs(jobs);
+    if let Some(scaling) = scaling(args) {
+        job = job.scaling(scaling);
     }
+    if let Some(shape) = args.wipe {
+        let (x, y) = args.wipe_center;
+        job = job.wipe(
+            Wipe::new(shape)
+                .angle(args.wipe_angle)?
+                .center(x, y)?
+                .softness(args.wipe_softness),
+        );
+    }
+    job = match args.effect {
+        None => job,
+        Some(EffectName::Desaturate) => job.effect(Desaturate),
+        Some(EffectName::Sepia) => job.effect(Sepia),
+        Some(EffectName::Contrast) => job.effect(Contrast),
+        Some(EffectName::Exposure) => job.effect(args.stops),
+        Some(EffectName::Temperature) => job.effect(args.kelvin),
+    };
+    if let Some(luma_matte) = luma_matte {
+        job = job.luma_matte(luma_matte.clone());
+    }
+    if let Some(noise) = args.dissolve {
+        job = job.dissolve(
+            Dissolve::new(noise)
+                .seed(args.seed)
+                .grain(args.grain)
+                .softness(args.dissolve_softness),
+        );
+    }
+    // Counting the frames rejects an impossible timeline before the outputs
+    // are created.
+    let frame_count = job.frame_count()?;
+    job.write_to(&mut open_sinks(args, output, frame_count, encoder)?)
+}
 
-    DynamicImage::ImageRgba8(output)
+/// Open the video encoder and image sequence that `output` asks for, for
+/// `frame_count` frames.
+fn open_sinks(
+    args: &Args,
+    output: &Output,
+    frame_count: usize,
+    encoder: &EncoderSettings,
+) -> Result<Vec<Box<dyn FrameSink>>, FaderError> {
+    let mut sinks = Vec::new();
+    if let Some(video) = &output.video {
+        sinks.push(open_sink(video, args.framerate, frame_count, encoder)?);
+    }
+    if let Some(dir) = &output.frames_dir {
+        let sequence = ImageSequenceSink::create(dir, args.frame_format)?
+            .pattern(args.frame_pattern.clone())
+            .jpeg_quality(args.jpeg_quality);
+        sinks.push(Box::new(sequence));
+    }
+    Ok(sinks)
+}
+
+/// Scaling to the --size of the output, if one was given.
+fn scaling(args: &Args) -> Option<Scaling> {
+    Some(
+        Scaling::new(args.size?)
+            .fit(args.fit)
+            .pad_color(args.pad_color)
+            .resampling(args.resampling),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn args(extra: &[&str]) -> Args {
+        Args::try_parse_from(["fader"].iter().chain(extra)).unwrap()
+    }
+
+    #[test]
+    fn arguments_are_consistent() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn accepts_known_placeholders() {
+        for template in [
+            "{stem}.mp4",
+            "{stem}-{style}-{index}.{ext}",
+            "plain.webm",
+            "",
+        ] {
+            assert_eq!(parse_name_template(template).as_deref(), Ok(template));
+        }
+        for invalid in [
+            "{stem",
+            "{name}.mp4",
+            "{stem}-{}.mp4",
+            "{STEM}.mp4",
+            "{{stem}}",
+        ] {
+            assert!(parse_name_template(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn names_outputs_after_the_template() {
+        let input = Path::new("backgrounds/beach.jpg");
+        let batch = args(&[
+            "--style",
+            "to-dark-and-back",
+            "--output-dir",
+            "videos",
+            "--name-template",
+            "{stem}-{style}-{index}.{ext}.mp4",
+            "beach.jpg",
+        ]);
+        assert_eq!(
+            output_path(&batch, input, 2, false),
+            Path::new("videos/beach-to-dark-and-back-3.jpg.mp4")
+        );
+
+        let defaults = args(&["beach.jpg"]);
+        assert_eq!(
+            output_path(&defaults, input, 0, false),
+            Path::new("beach.mp4")
+        );
+        assert_eq!(
+            output_path(&defaults, input, 0, true),
+            Path::new("beach.webm")
+        );
+    }
 }
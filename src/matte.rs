@@ -0,0 +1,174 @@
+use image::{
+    DynamicImage, ImageBuffer, Luma,
+    imageops::{self, FilterType},
+};
+use std::path::Path;
+
+use crate::{Result, error::open_image};
+
+/// When each pixel of a frame fades, for transitions that sweep across the
+/// image instead of fading it evenly.
+///
+/// Every pixel has a position from `0.0`, fading first, to `1.0`, fading
+/// last. As the fade factor goes from `1.0` to `0.0`, an edge travels across
+/// the positions and fades the pixels behind it, blending them over a band of
+/// `softness` positions.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Matte {
+    width: u32,
+    height: u32,
+    positions: Vec<f32>,
+    softness: f32,
+}
+
+impl Matte {
+    /// A matte of `width` by `height` pixels, positioned row by row.
+    pub(crate) fn new(width: u32, height: u32, positions: Vec<f32>, softness: f32) -> Self {
+        debug_assert_eq!(positions.len(), width as usize * height as usize);
+        Self {
+            width,
+            height,
+            positions,
+            // A hard edge is a very narrow soft one, so that the first and
+            // last positions are still covered at either end of the fade.
+            // A NaN softness is taken as a hard edge too.
+            softness: if softness.is_nan() { 0.0 } else { softness }.clamp(f32::EPSILON, 1.0),
+        }
+    }
+
+    pub(crate) fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The weight on the image of every pixel, in order, for the fade factor
+    /// `alpha`: `1.0` where the image is untouched and `0.0` where it is
+    /// fully faded.
+    pub(crate) fn weights(&self, alpha: f32) -> impl Iterator<Item = f32> + '_ {
+        let coverage = 1.0 - alpha.clamp(0.0, 1.0);
+        // The edge starts a band before the first position and ends on the last.
+        let edge = coverage + (coverage - 1.0) * self.softness;
+        self.positions.iter().map(move |&position| {
+            let t = ((position - edge) / self.softness).clamp(0.0, 1.0);
+            t * t * (3.0 - 2.0 * t)
+        })
+    }
+}
+
+/// A grayscale image deciding when each pixel fades: dark areas of the mask
+/// fade first and bright ones last, so any transition drawn in an image
+/// editor can drive a fade.
+///
+/// The mask is stretched to the size of the frames and only its luminance
+/// counts. As with wipes, the fade style decides the direction, `from-dark`
+/// uncovering bright areas first.
+///
+/// ```no_run
+/// use fader::{FadeJob, LumaMatte};
+///
+/// let job = FadeJob::open("background.png")?
+///     .luma_matte(LumaMatte::open("transition.png")?.softness(0.2));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct LumaMatte {
+    mask: ImageBuffer<Luma<u16>, Vec<u16>>,
+    softness: f32,
+}
+
+impl LumaMatte {
+    /// Use the luminance of `mask`, blending over a tenth of the fade.
+    pub fn new(mask: DynamicImage) -> Self {
+        Self {
+            mask: mask.into_luma16(),
+            softness: 0.1,
+        }
+    }
+
+    /// Decode the mask at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(open_image(path.as_ref())?))
+    }
+
+    /// Range of luminance blended at once, from `0.0` for pixels that switch
+    /// abruptly to `1.0`. Soft mattes also hide the steps of 8-bit masks.
+    pub fn softness(mut self, softness: f32) -> Self {
+        self.softness = softness.clamp(0.0, 1.0);
+        self
+    }
+
+    /// The order pixels of a `width` by `height` frame are faded in.
+    pub(crate) fn matte(&self, width: u32, height: u32) -> Matte {
+        let resized;
+        let mask = if self.mask.dimensions() == (width, height) {
+            &self.mask
+        } else {
+            resized = imageops::resize(&self.mask, width, height, FilterType::Triangle);
+            &resized
+        };
+        let positions = mask
+            .iter()
+            .map(|&luma| luma as f32 / u16::MAX as f32)
+            .collect();
+        Matte::new(width, height, positions, self.softness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    fn weights(matte: &Matte, alpha: f32) -> Vec<f32> {
+        matte.weights(alpha).collect()
+    }
+
+    #[test]
+    fn fades_every_position_between_the_ends() {
+        let positions = vec![0.0, 0.25, 0.5, 1.0];
+        for softness in [0.0, 0.3, 1.0, f32::NAN] {
+            let matte = Matte::new(4, 1, positions.clone(), softness);
+            assert_eq!(weights(&matte, 1.0), [1.0; 4], "{softness}");
+            assert_eq!(weights(&matte, 0.0), [0.0; 4], "{softness}");
+        }
+    }
+
+    #[test]
+    fn soft_edges_fade_gradually() {
+        let matte = Matte::new(3, 1, vec![0.0, 0.5, 1.0], 0.5);
+        let mut previous = vec![1.0; 3];
+        for step in (0..=20).rev() {
+            let current = weights(&matte, step as f32 / 20.0);
+            for (current, previous) in current.iter().zip(&previous) {
+                assert!(current <= previous, "{current} > {previous}");
+            }
+            // Later positions are never more faded than earlier ones.
+            assert!(
+                current.windows(2).all(|pair| pair[0] <= pair[1]),
+                "{current:?}"
+            );
+            previous = current;
+        }
+        let halfway = weights(&matte, 0.5)[1];
+        assert!(0.0 < halfway && halfway < 1.0, "{halfway}");
+    }
+
+    #[test]
+    fn nan_softness_is_a_hard_edge() {
+        let hard = Matte::new(2, 1, vec![0.2, 0.8], 0.0);
+        let nan = Matte::new(2, 1, vec![0.2, 0.8], f32::NAN);
+        assert_eq!(hard, nan);
+        assert_eq!(weights(&nan, 0.5), [0.0, 1.0]);
+    }
+
+    #[test]
+    fn dark_areas_of_luma_mattes_fade_first() {
+        let mask = ImageBuffer::from_fn(2, 1, |x, _| Luma([x as u8 * 255]));
+        let luma_matte = LumaMatte::new(DynamicImage::ImageLuma8(mask)).softness(0.0);
+        assert_eq!(weights(&luma_matte.matte(2, 1), 0.5), [0.0, 1.0]);
+
+        let stretched = luma_matte.matte(4, 2);
+        assert_eq!(stretched.dimensions(), (4, 2));
+        let weights = weights(&stretched, 0.5);
+        assert_eq!((weights[0], weights[3]), (0.0, 1.0));
+    }
+}
@@ -0,0 +1,204 @@
+use std::sync::LazyLock;
+
+/// Side of the square, tileable [`BLUE_NOISE`] texture.
+pub(crate) const BLUE_NOISE_SIZE: usize = 64;
+
+/// Small, fast pseudo-random generator (SplitMix64), so that noise is the
+/// same on every platform for a given seed.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed index below `bound`.
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// Rank of every cell of a blue noise texture, row by row, from `0` to
+/// `BLUE_NOISE_SIZE² - 1`. Thresholding the ranks at any level gives evenly
+/// spread points without clumps or regular structure, and the texture tiles
+/// seamlessly.
+pub(crate) static BLUE_NOISE: LazyLock<Vec<u16>> = LazyLock::new(void_and_cluster);
+
+/// Energy of points around a cell on the torus, indexed by the wrapped offset
+/// to the point. A wider kernel spreads the points more evenly.
+fn kernel() -> Vec<f32> {
+    const SIGMA: f32 = 1.5;
+    let n = BLUE_NOISE_SIZE;
+    let wrapped = |d: usize| d.min(n - d) as f32;
+    (0..n * n)
+        .map(|i| {
+            let (dx, dy) = (wrapped(i % n), wrapped(i / n));
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect()
+}
+
+/// Binary pattern on the torus along with the energy of every cell, the sum
+/// of the kernel over all points.
+#[derive(Clone)]
+struct Pattern<'a> {
+    kernel: &'a [f32],
+    points: Vec<bool>,
+    energy: Vec<f32>,
+}
+
+impl Pattern<'_> {
+    fn toggle(&mut self, cell: usize) {
+        let n = BLUE_NOISE_SIZE;
+        self.points[cell] = !self.points[cell];
+        let sign = if self.points[cell] { 1.0 } else { -1.0 };
+        let (x, y) = (cell % n, cell / n);
+        for (row, energy) in self.energy.chunks_exact_mut(n).enumerate() {
+            let dy = (row + n - y) % n;
+            let kernel = &self.kernel[dy * n..(dy + 1) * n];
+            // Cells right of the point see the start of the kernel row, the
+            // ones left of it its wrapped-around end.
+            let (left, right) = energy.split_at_mut(x);
+            for (e, k) in right.iter_mut().zip(&kernel[..n - x]) {
+                *e += sign * k;
+            }
+            for (e, k) in left.iter_mut().zip(&kernel[n - x..]) {
+                *e += sign * k;
+            }
+        }
+    }
+
+    /// The point with the most energy, where points are packed densest.
+    fn tightest_cluster(&self) -> usize {
+        self.extreme(true, |a, b| a > b)
+    }
+
+    /// The empty cell with the least energy, farthest from all points.
+    fn largest_void(&self) -> usize {
+        self.extreme(false, |a, b| a < b)
+    }
+
+    fn extreme(&self, point: bool, better: impl Fn(f32, f32) -> bool) -> usize {
+        let mut best = None;
+        for (cell, &energy) in self.energy.iter().enumerate() {
+            if self.points[cell] == point && best.is_none_or(|(_, e)| better(energy, e)) {
+                best = Some((cell, energy));
+            }
+        }
+        best.map_or(0, |(cell, _)| cell)
+    }
+}
+
+/// Rank the cells of the texture with Ulichney's void-and-cluster method.
+fn void_and_cluster() -> Vec<u16> {
+    let n = BLUE_NOISE_SIZE * BLUE_NOISE_SIZE;
+    let kernel = kernel();
+    let mut initial = Pattern {
+        kernel: &kernel,
+        points: vec![false; n],
+        energy: vec![0.0; n],
+    };
+
+    // Start from a tenth of the cells at random, then move points from the
+    // tightest cluster to the largest void until that changes nothing.
+    let mut rng = Rng::new(0x5eed);
+    let mut placed = 0;
+    while placed < n / 10 {
+        let cell = rng.below(n);
+        if !initial.points[cell] {
+            initial.toggle(cell);
+            placed += 1;
+        }
+    }
+    for _ in 0..n {
+        let cluster = initial.tightest_cluster();
+        initial.toggle(cluster);
+        let void = initial.largest_void();
+        initial.toggle(void);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; n];
+    // The initial points are ranked by removing the tightest clusters first...
+    let mut pattern = initial.clone();
+    for rank in (0..placed).rev() {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+        ranks[cluster] = rank as u16;
+    }
+    // ...and the other cells by filling the largest voids.
+    let mut pattern = initial;
+    for rank in placed..n {
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        ranks[void] = rank as u16;
+    }
+    ranks
+}
+
+/// Uniformly distributed value in `0.0..1.0` for the cell at `x`, `y`, the
+/// same for the same `seed` however the cells are visited.
+pub(crate) fn white_noise(seed: u64, x: u32, y: u32) -> f32 {
+    let cell = (u64::from(x) << 32) | u64::from(y);
+    let bits = Rng::new(seed ^ cell.wrapping_mul(0x2545_f491_4f6c_dd1d)).next_u64();
+    (bits >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Classic two-dimensional gradient noise, with a lattice shuffled by a seed.
+pub(crate) struct Perlin {
+    permutation: [u8; 512],
+}
+
+impl Perlin {
+    pub(crate) fn new(seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let mut shuffled: [u8; 256] = std::array::from_fn(|i| i as u8);
+        for i in (1..shuffled.len()).rev() {
+            shuffled.swap(i, rng.below(i + 1));
+        }
+        Self {
+            permutation: std::array::from_fn(|i| shuffled[i % 256]),
+        }
+    }
+
+    /// Noise at `x`, `y` in lattice units, roughly within `-1.0..=1.0`.
+    pub(crate) fn noise(&self, x: f32, y: f32) -> f32 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (xi, yi) = (x0.rem_euclid(256.0) as usize, y0.rem_euclid(256.0) as usize);
+        let p = &self.permutation;
+        let corner = |dx: usize, dy: usize| {
+            let hash = p[p[xi + dx] as usize + yi + dy];
+            let (gx, gy) = match hash & 7 {
+                0 => (1.0, 0.0),
+                1 => (-1.0, 0.0),
+                2 => (0.0, 1.0),
+                3 => (0.0, -1.0),
+                4 => (1.0, 1.0),
+                5 => (-1.0, 1.0),
+                6 => (1.0, -1.0),
+                _ => (-1.0, -1.0),
+            };
+            gx * (fx - dx as f32) + gy * (fy - dy as f32)
+        };
+        let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let (u, v) = (fade(fx), fade(fy));
+        lerp(
+            lerp(corner(0, 0), corner(1, 0), u),
+            lerp(corner(0, 1), corner(1, 1), u),
+            v,
+        )
+    }
+}
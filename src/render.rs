@@ -0,0 +1,306 @@
+use image::{DynamicImage, ImageBuffer, Rgba, RgbaImage};
+
+use crate::{
+    Color, ColorSpace, Dither, Effect, Rgba16Image,
+    colorspace::{mix_oklab, srgb_to_oklab},
+    dither::{Quantize, Thresholds, to_fixed},
+    matte::Matte,
+};
+
+/// An RGBA image that frames are rendered into: an [`RgbaImage`], or an
+/// [`Rgba16Image`] for 16 bits per channel. Frames are quantized to the full
+/// precision of the channels.
+pub trait FrameBuffer: Buffer {}
+
+impl FrameBuffer for RgbaImage {}
+impl FrameBuffer for Rgba16Image {}
+
+/// What rendering needs of a [`FrameBuffer`]. Public in name only, so that
+/// nothing else can be a [`FrameBuffer`].
+pub trait Buffer: Default + Send + Sync {
+    /// Type of each channel, `u8` or `u16`.
+    type Channel: Quantize;
+
+    /// Resize to `width` by `height`, reusing the allocation when the size
+    /// already matches.
+    fn match_dimensions(&mut self, width: u32, height: u32);
+
+    /// The channels of every pixel, row by row.
+    fn channels_mut(&mut self) -> &mut [Self::Channel];
+}
+
+impl<C: Quantize> Buffer for ImageBuffer<Rgba<C>, Vec<C>>
+where
+    Rgba<C>: image::Pixel<Subpixel = C>,
+{
+    type Channel = C;
+
+    fn match_dimensions(&mut self, width: u32, height: u32) {
+        if self.dimensions() != (width, height) {
+            *self = ImageBuffer::new(width, height);
+        }
+    }
+
+    fn channels_mut(&mut self) -> &mut [C] {
+        self
+    }
+}
+
+/// Make `dst` the same size as `src`, reusing its allocation when it already is.
+pub(crate) fn match_dimensions(src: &RgbaImage, dst: &mut impl FrameBuffer) {
+    dst.match_dimensions(src.width(), src.height());
+}
+
+/// Copy `src` into `dst`, widening its channels to those of `dst`. `dst` is
+/// resized to match `src` if needed.
+pub(crate) fn copy_into<F: FrameBuffer>(src: &RgbaImage, dst: &mut F) {
+    match_dimensions(src, dst);
+    for (to, &from) in dst.channels_mut().iter_mut().zip(src.iter()) {
+        *to = F::Channel::widen(from);
+    }
+}
+
+/// Pixels of `src` and `dst` along with the dither threshold of each.
+pub(crate) fn pixels<'a, F: FrameBuffer>(
+    src: &'a RgbaImage,
+    dst: &'a mut F,
+    thresholds: &'a Thresholds,
+) -> impl Iterator<Item = (&'a [u8], &'a mut [F::Channel], u8)> {
+    let stride = (src.width() as usize * 4).max(1);
+    src.chunks_exact(stride)
+        .zip(dst.channels_mut().chunks_exact_mut(stride))
+        .enumerate()
+        .flat_map(move |(y, (from, to))| {
+            let row = thresholds.row(y);
+            from.chunks_exact(4)
+                .zip(to.chunks_exact_mut(4))
+                .enumerate()
+                .map(move |(x, (from, to))| (from, to, row[x % row.len()]))
+        })
+}
+
+/// Blend the RGB channels of `src` towards `color` in `space` into `dst`,
+/// leaving the alpha channel intact. `dst` is resized to match `src` if needed.
+///
+/// An `alpha` of `1.0` keeps the image unchanged while `0.0` yields a solid
+/// `color`. The result is quantized with `dither`, whose pattern may depend on
+/// the index of the frame in the video, to the channels of a [`FrameBuffer`].
+pub fn fade_image_into<F: FrameBuffer>(
+    src: &RgbaImage,
+    alpha: f32,
+    color: Color,
+    space: ColorSpace,
+    dither: Dither,
+    frame_index: usize,
+    dst: &mut F,
+) {
+    match_dimensions(src, dst);
+    let thresholds = dither.thresholds(frame_index);
+    let pixels = pixels(src, dst, &thresholds);
+
+    if let [Some(r), Some(g), Some(b)] = color.channels().map(|c| space.mix_table(c, alpha)) {
+        for (from, to, threshold) in pixels {
+            to[0] = F::Channel::quantize_fixed(r[from[0] as usize], threshold);
+            to[1] = F::Channel::quantize_fixed(g[from[1] as usize], threshold);
+            to[2] = F::Channel::quantize_fixed(b[from[2] as usize], threshold);
+            to[3] = F::Channel::widen(from[3]);
+        }
+    } else {
+        // Only OKLab mixes channels, convert the fade color just once.
+        let target = srgb_to_oklab(color.channels());
+        for (from, to, threshold) in pixels {
+            let rgb = mix_oklab(target, [from[0], from[1], from[2]], alpha);
+            for (to, value) in to.iter_mut().zip(rgb) {
+                *to = F::Channel::quantize(value, threshold);
+            }
+            to[3] = F::Channel::widen(from[3]);
+        }
+    }
+}
+
+/// Blend the RGB channels of `img` towards `color` in `space`, leaving its
+/// alpha channel intact and rounding without dithering.
+///
+/// An `alpha` of `1.0` keeps the image unchanged while `0.0` yields a solid
+/// `color`. [`ColorFade`](crate::ColorFade) is the same fade as an [`Effect`].
+pub fn fade_image(img: &DynamicImage, alpha: f32, color: Color, space: ColorSpace) -> DynamicImage {
+    let mut output = RgbaImage::default();
+    fade_image_into(
+        &img.to_rgba8(),
+        alpha,
+        color,
+        space,
+        Dither::None,
+        0,
+        &mut output,
+    );
+    DynamicImage::ImageRgba8(output)
+}
+
+/// Apply `effect` to the RGB channels of `src` at `factor` into `dst`, leaving
+/// the alpha channel intact. `dst` is resized to match `src` if needed.
+///
+/// A `factor` of `1.0` keeps the image unchanged while `0.0` applies the
+/// effect fully. The result is quantized with `dither`, like
+/// [`fade_image_into`].
+pub fn apply_effect_into<F: FrameBuffer>(
+    src: &RgbaImage,
+    effect: &dyn Effect,
+    factor: f32,
+    dither: Dither,
+    frame_index: usize,
+    dst: &mut F,
+) {
+    match_dimensions(src, dst);
+    let thresholds = dither.thresholds(frame_index);
+
+    for (from, to, threshold) in pixels(src, dst, &thresholds) {
+        let rgb = effect.apply([from[0], from[1], from[2]], factor);
+        for (to, value) in to.iter_mut().zip(rgb) {
+            *to = F::Channel::quantize(value, threshold);
+        }
+        to[3] = F::Channel::widen(from[3]);
+    }
+}
+
+/// Apply `effect` to the RGB channels of `img` at `factor`, leaving its alpha
+/// channel intact and rounding without dithering.
+///
+/// A `factor` of `1.0` keeps the image unchanged while `0.0` applies the
+/// effect fully.
+pub fn apply_effect(img: &DynamicImage, effect: &dyn Effect, factor: f32) -> DynamicImage {
+    let mut output = RgbaImage::default();
+    apply_effect_into(
+        &img.to_rgba8(),
+        effect,
+        factor,
+        Dither::None,
+        0,
+        &mut output,
+    );
+    DynamicImage::ImageRgba8(output)
+}
+
+/// Fade `src` into `dst` like [`apply_effect_into`] or, without an `effect`,
+/// like [`fade_opacity_into`], but fading each pixel when `matte` says so
+/// rather than all at once.
+///
+/// `matte` must have the dimensions of `src`.
+pub(crate) fn fade_matte_into<F: FrameBuffer>(
+    src: &RgbaImage,
+    matte: &Matte,
+    alpha: f32,
+    effect: Option<&dyn Effect>,
+    dither: Dither,
+    frame_index: usize,
+    dst: &mut F,
+) {
+    debug_assert_eq!(matte.dimensions(), src.dimensions());
+    match_dimensions(src, dst);
+    let thresholds = dither.thresholds(frame_index);
+
+    for ((from, to, threshold), weight) in pixels(src, dst, &thresholds).zip(matte.weights(alpha)) {
+        for (to, &from) in to.iter_mut().zip(from) {
+            *to = F::Channel::widen(from);
+        }
+        if weight >= 1.0 {
+            continue;
+        }
+        match effect {
+            Some(effect) => {
+                let rgb = effect.apply([from[0], from[1], from[2]], weight);
+                for (to, value) in to.iter_mut().zip(rgb) {
+                    *to = F::Channel::quantize(value, threshold);
+                }
+            }
+            None => to[3] = F::Channel::quantize(from[3] as f32 * weight, threshold),
+        }
+    }
+}
+
+/// Dissolve from `from` into `to` in `space`, writing the result into `dst`,
+/// where `t` of `0.0` is `from` and `1.0` is `to`. `dst` is resized to match
+/// `from` if needed. The result is quantized with `dither`, like
+/// [`fade_image_into`].
+///
+/// Both images must have the same dimensions.
+pub fn blend_images_into<F: FrameBuffer>(
+    from: &RgbaImage,
+    to: &RgbaImage,
+    t: f32,
+    space: ColorSpace,
+    dither: Dither,
+    frame_index: usize,
+    dst: &mut F,
+) {
+    match_dimensions(from, dst);
+    let thresholds = dither.thresholds(frame_index);
+    let lerp = |a: u8, b: u8| a as f32 + (b as f32 - a as f32) * t;
+
+    for ((a, out, threshold), b) in pixels(from, dst, &thresholds).zip(to.chunks_exact(4)) {
+        if space == ColorSpace::Naive {
+            for c in 0..4 {
+                out[c] = F::Channel::quantize(lerp(a[c], b[c]), threshold);
+            }
+        } else {
+            let rgb = space.mix([a[0], a[1], a[2]], [b[0], b[1], b[2]], t);
+            for (out, value) in out.iter_mut().zip(rgb) {
+                *out = F::Channel::quantize(value, threshold);
+            }
+            out[3] = F::Channel::quantize(lerp(a[3], b[3]), threshold);
+        }
+    }
+}
+
+/// Dissolve from `from` into `to` in `space`, where `t` of `0.0` is `from`
+/// and `1.0` is `to`, rounding without dithering.
+///
+/// Both images must have the same dimensions.
+pub fn blend_images(
+    from: &DynamicImage,
+    to: &DynamicImage,
+    t: f32,
+    space: ColorSpace,
+) -> DynamicImage {
+    let mut output = RgbaImage::default();
+    blend_images_into(
+        &from.to_rgba8(),
+        &to.to_rgba8(),
+        t,
+        space,
+        Dither::None,
+        0,
+        &mut output,
+    );
+    DynamicImage::ImageRgba8(output)
+}
+
+/// Scale the alpha channel of `src` by `opacity` into `dst`, leaving the colors
+/// intact. `dst` is resized to match `src` if needed. The result is quantized
+/// with `dither`, like [`fade_image_into`].
+pub fn fade_opacity_into<F: FrameBuffer>(
+    src: &RgbaImage,
+    opacity: f32,
+    dither: Dither,
+    frame_index: usize,
+    dst: &mut F,
+) {
+    match_dimensions(src, dst);
+    let thresholds = dither.thresholds(frame_index);
+    let a: [u16; 256] = std::array::from_fn(|v| to_fixed(v as f32 * opacity));
+
+    for (from, to, threshold) in pixels(src, dst, &thresholds) {
+        for (to, &from) in to[..3].iter_mut().zip(&from[..3]) {
+            *to = F::Channel::widen(from);
+        }
+        to[3] = F::Channel::quantize_fixed(a[from[3] as usize], threshold);
+    }
+}
+
+/// Scale the alpha channel of `img` by `opacity`, leaving its colors intact
+/// and rounding without dithering.
+pub fn fade_opacity(img: &DynamicImage, opacity: f32) -> DynamicImage {
+    let mut output = RgbaImage::default();
+    fade_opacity_into(&img.to_rgba8(), opacity, Dither::None, 0, &mut output);
+    DynamicImage::ImageRgba8(output)
+}
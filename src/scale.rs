@@ -0,0 +1,257 @@
+use clap::ValueEnum;
+use image::{
+    Rgba, RgbaImage,
+    imageops::{self, FilterType},
+};
+use std::{borrow::Cow, fmt, str::FromStr};
+
+use crate::Color;
+
+/// Dimensions of the output video, written as `WIDTHxHEIGHT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Size {
+    pub const fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    /// Parse `WIDTHxHEIGHT`, such as `1920x1080`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s
+            .split_once(['x', 'X'])
+            .ok_or_else(|| format!("expected WIDTHxHEIGHT, got `{s}`"))?;
+        let dimension = |text: &str| match text.trim().parse::<u32>() {
+            Ok(0) | Err(_) => Err(format!("invalid dimension `{text}` in `{s}`")),
+            Ok(n) => Ok(n),
+        };
+        Ok(Size::new(dimension(width)?, dimension(height)?))
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// How an image is fitted into output dimensions of another aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Fit {
+    /// Scale the image to fit inside the dimensions, keeping its aspect ratio.
+    /// The video is smaller than requested along one axis, and other images
+    /// of a crossfade are stretched to the size of the first one.
+    Contain,
+    /// Scale the image to cover the dimensions, keeping its aspect ratio, and
+    /// crop what sticks out evenly on both sides.
+    Cover,
+    /// Scale the image to exactly the dimensions, distorting it.
+    Stretch,
+    /// Like `contain`, then center the image on a background of the pad color.
+    #[default]
+    Pad,
+}
+
+/// Filter used to resample images when they are scaled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Resampling {
+    /// Nearest neighbor: blocky, but keeps pixel art crisp.
+    Nearest,
+    /// Linear interpolation between the nearest pixels.
+    Bilinear,
+    /// Catmull-Rom cubic interpolation.
+    Bicubic,
+    /// Gaussian filter, softening the image slightly.
+    Gaussian,
+    /// Lanczos with a window of 3: the sharpest, and the slowest.
+    #[default]
+    Lanczos,
+}
+
+impl Resampling {
+    fn filter(self) -> FilterType {
+        match self {
+            Resampling::Nearest => FilterType::Nearest,
+            Resampling::Bilinear => FilterType::Triangle,
+            Resampling::Bicubic => FilterType::CatmullRom,
+            Resampling::Gaussian => FilterType::Gaussian,
+            Resampling::Lanczos => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// Scaling of the input images to the size of the output video.
+///
+/// ```
+/// use fader::{Color, Fit, Scaling, Size};
+///
+/// let scaling = Scaling::new(Size::new(1920, 1080))
+///     .fit(Fit::Pad)
+///     .pad_color(Color::WHITE);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scaling {
+    size: Size,
+    fit: Fit,
+    resampling: Resampling,
+    pad_color: Color,
+}
+
+impl Scaling {
+    /// Scale images to `size`, padding them with black where their aspect ratio
+    /// differs and resampling with Lanczos.
+    pub fn new(size: Size) -> Self {
+        Self {
+            size,
+            fit: Fit::default(),
+            resampling: Resampling::default(),
+            pad_color: Color::BLACK,
+        }
+    }
+
+    /// How images of another aspect ratio are fitted.
+    pub fn fit(mut self, fit: Fit) -> Self {
+        self.fit = fit;
+        self
+    }
+
+    /// Filter used to resample the images.
+    pub fn resampling(mut self, resampling: Resampling) -> Self {
+        self.resampling = resampling;
+        self
+    }
+
+    /// Background around images fitted with [`Fit::Pad`]. Defaults to black.
+    pub fn pad_color(mut self, pad_color: Color) -> Self {
+        self.pad_color = pad_color;
+        self
+    }
+
+    /// Scale `image` as configured.
+    pub fn apply(&self, image: &RgbaImage) -> RgbaImage {
+        let Size { width, height } = self.size;
+        let filter = self.resampling.filter();
+        if self.fit == Fit::Stretch {
+            return imageops::resize(image, width, height, filter);
+        }
+
+        let (image_width, image_height) = (image.width() as f64, image.height() as f64);
+        let (x_scale, y_scale) = (width as f64 / image_width, height as f64 / image_height);
+        let scale = match self.fit {
+            Fit::Cover => x_scale.max(y_scale),
+            _ => x_scale.min(y_scale),
+        };
+        let scaled_width = ((image_width * scale).round() as u32).max(1);
+        let scaled_height = ((image_height * scale).round() as u32).max(1);
+        let scaled = imageops::resize(image, scaled_width, scaled_height, filter);
+
+        match self.fit {
+            Fit::Cover => {
+                let x = scaled_width.saturating_sub(width) / 2;
+                let y = scaled_height.saturating_sub(height) / 2;
+                imageops::crop_imm(&scaled, x, y, width, height).to_image()
+            }
+            Fit::Pad => {
+                let [r, g, b] = self.pad_color.channels();
+                let mut padded = RgbaImage::from_pixel(width, height, Rgba([r, g, b, 255]));
+                let x = width.saturating_sub(scaled_width) / 2;
+                let y = height.saturating_sub(scaled_height) / 2;
+                imageops::replace(&mut padded, &scaled, x.into(), y.into());
+                padded
+            }
+            _ => scaled,
+        }
+    }
+}
+
+/// Bring `images` to a common size: that of the first image after `scaling`,
+/// which the others are stretched to if they still differ.
+///
+/// Images already of the right size are borrowed rather than copied.
+pub(crate) fn uniform<'a>(
+    images: &'a [RgbaImage],
+    scaling: Option<&Scaling>,
+) -> Vec<Cow<'a, RgbaImage>> {
+    let scaled: Vec<Cow<RgbaImage>> = match scaling {
+        Some(scaling) => images
+            .iter()
+            .map(|image| Cow::Owned(scaling.apply(image)))
+            .collect(),
+        None => images.iter().map(Cow::Borrowed).collect(),
+    };
+    let Some((width, height)) = scaled.first().map(|first| first.dimensions()) else {
+        return scaled;
+    };
+    scaled
+        .into_iter()
+        .map(|image| {
+            if image.dimensions() == (width, height) {
+                image
+            } else {
+                Cow::Owned(imageops::resize(
+                    &*image,
+                    width,
+                    height,
+                    FilterType::Lanczos3,
+                ))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!("1920x1080".parse(), Ok(Size::new(1920, 1080)));
+        assert_eq!("640X480".parse(), Ok(Size::new(640, 480)));
+        assert_eq!(" 2 x 1 ".parse(), Ok(Size::new(2, 1)));
+        assert_eq!(Size::new(1280, 720).to_string(), "1280x720");
+        for invalid in [
+            "1920",
+            "0x1080",
+            "1920x0",
+            "-1x5",
+            "axb",
+            "1920x1080x3",
+            "x",
+        ] {
+            assert!(invalid.parse::<Size>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn fits_images_into_the_size() {
+        let image = RgbaImage::from_pixel(40, 20, Rgba([255, 255, 255, 255]));
+        let scaled = |fit| Scaling::new(Size::new(30, 30)).fit(fit).apply(&image);
+        assert_eq!(scaled(Fit::Stretch).dimensions(), (30, 30));
+        assert_eq!(scaled(Fit::Contain).dimensions(), (30, 15));
+        assert_eq!(scaled(Fit::Cover).dimensions(), (30, 30));
+
+        let padded = scaled(Fit::Pad);
+        assert_eq!(padded.dimensions(), (30, 30));
+        assert_eq!(padded.get_pixel(15, 0), &Rgba([0, 0, 0, 255]));
+        assert_eq!(padded.get_pixel(15, 15), &Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn uniform_stretches_to_the_first_image() {
+        let images = [
+            RgbaImage::new(4, 2),
+            RgbaImage::new(4, 2),
+            RgbaImage::new(3, 3),
+        ];
+        let uniform = uniform(&images, None);
+        assert!(matches!(uniform[1], Cow::Borrowed(_)));
+        assert!(uniform.iter().all(|image| image.dimensions() == (4, 2)));
+    }
+}
@@ -0,0 +1,220 @@
+use clap::ValueEnum;
+
+use crate::Easing;
+
+/// Shape of the brightness curve over the course of the fade.
+///
+/// "Dark" is the fade color, which is black unless another one is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum FadeStyle {
+    #[default]
+    ToDark,
+    FromDark,
+    ToDarkAndBack,
+    FromDarkAndBack,
+}
+
+impl FadeStyle {
+    /// Whether the fade reverses halfway, returning to where it started.
+    pub fn turns_around(self) -> bool {
+        matches!(self, FadeStyle::ToDarkAndBack | FadeStyle::FromDarkAndBack)
+    }
+}
+
+/// Compute the per-frame brightness factor for `frame_count` frames.
+///
+/// `easing` shapes every ramp of the style, whether it brightens or darkens.
+/// A ramp squeezed into a single frame shows where it starts, so short
+/// timelines never divide by zero.
+pub fn fade_factors(style: FadeStyle, easing: Easing, frame_count: usize) -> Vec<f32> {
+    let up = |n: usize| -> Vec<f32> {
+        let last = n.saturating_sub(1).max(1) as f32;
+        (0..n).map(|i| easing.apply(i as f32 / last)).collect()
+    };
+    let down = |n: usize| -> Vec<f32> { up(n).into_iter().map(|f| 1.0 - f).collect() };
+
+    let half = turnaround(frame_count, false);
+    match style {
+        FadeStyle::ToDark => down(frame_count),
+        FadeStyle::FromDark => up(frame_count),
+        FadeStyle::ToDarkAndBack => [down(half), up(frame_count - half)].concat(),
+        FadeStyle::FromDarkAndBack => [up(half), down(frame_count - half)].concat(),
+    }
+}
+
+/// Compute the per-frame brightness factor for `frame_count` frames of a
+/// fade that repeats seamlessly.
+///
+/// Unlike [`fade_factors`], there-and-back styles stop one step short of
+/// where they started, so the last frame leads into the first without
+/// showing the same frame twice. The turnaround always falls on a frame; with
+/// an odd frame count the way back takes one step more than the way there.
+/// One-way styles have no such duplicate and are returned unchanged.
+pub fn cyclic_fade_factors(style: FadeStyle, easing: Easing, frame_count: usize) -> Vec<f32> {
+    if !style.turns_around() {
+        return fade_factors(style, easing, frame_count);
+    }
+    let peak = frame_count / 2;
+    (0..frame_count)
+        .map(|i| {
+            let progress = if i <= peak {
+                i as f32 / peak.max(1) as f32
+            } else {
+                (frame_count - i) as f32 / (frame_count - peak) as f32
+            };
+            let away = easing.apply(progress);
+            match style {
+                FadeStyle::ToDarkAndBack => 1.0 - away,
+                _ => away,
+            }
+        })
+        .collect()
+}
+
+/// Index of the first frame after the turnaround of a there-and-back style.
+pub(crate) fn turnaround(frame_count: usize, cyclic: bool) -> usize {
+    match frame_count {
+        // A lone frame goes to the first ramp, so it shows where the fade starts.
+        1 => 1,
+        // The cyclic curve peaks on frame n / 2 rather than between two.
+        n if cyclic => n / 2 + 1,
+        n => n / 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STYLES: [FadeStyle; 4] = [
+        FadeStyle::ToDark,
+        FadeStyle::FromDark,
+        FadeStyle::ToDarkAndBack,
+        FadeStyle::FromDarkAndBack,
+    ];
+
+    fn factors(style: FadeStyle, frame_count: usize) -> Vec<f32> {
+        fade_factors(style, Easing::Linear, frame_count)
+    }
+
+    #[test]
+    fn no_frames() {
+        for style in STYLES {
+            assert!(factors(style, 0).is_empty(), "{style:?}");
+        }
+    }
+
+    #[test]
+    fn one_frame_shows_where_the_fade_starts() {
+        assert_eq!(factors(FadeStyle::ToDark, 1), [1.0]);
+        assert_eq!(factors(FadeStyle::FromDark, 1), [0.0]);
+        assert_eq!(factors(FadeStyle::ToDarkAndBack, 1), [1.0]);
+        assert_eq!(factors(FadeStyle::FromDarkAndBack, 1), [0.0]);
+    }
+
+    #[test]
+    fn two_frames() {
+        assert_eq!(factors(FadeStyle::ToDark, 2), [1.0, 0.0]);
+        assert_eq!(factors(FadeStyle::FromDark, 2), [0.0, 1.0]);
+        assert_eq!(factors(FadeStyle::ToDarkAndBack, 2), [1.0, 0.0]);
+        assert_eq!(factors(FadeStyle::FromDarkAndBack, 2), [0.0, 1.0]);
+    }
+
+    #[test]
+    fn three_frames() {
+        assert_eq!(factors(FadeStyle::ToDark, 3), [1.0, 0.5, 0.0]);
+        assert_eq!(factors(FadeStyle::FromDark, 3), [0.0, 0.5, 1.0]);
+        assert_eq!(factors(FadeStyle::ToDarkAndBack, 3), [1.0, 0.0, 1.0]);
+        assert_eq!(factors(FadeStyle::FromDarkAndBack, 3), [0.0, 1.0, 0.0]);
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?} != {expected:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-6, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn cycles_reach_the_turnaround_without_repeating_a_frame() {
+        let third = 1.0 / 3.0;
+        let expected: [&[f32]; 6] = [
+            &[1.0],
+            &[1.0, 0.0],
+            &[1.0, 0.0, 0.5],
+            &[1.0, 0.5, 0.0, 0.5],
+            &[1.0, 0.5, 0.0, third, 2.0 * third],
+            &[1.0, 2.0 * third, third, 0.0, third, 2.0 * third],
+        ];
+        for (frame_count, expected) in (1..=6).zip(expected) {
+            let there_and_back =
+                cyclic_fade_factors(FadeStyle::ToDarkAndBack, Easing::Linear, frame_count);
+            assert_close(&there_and_back, expected);
+            assert_eq!(turnaround(frame_count, true), frame_count / 2 + 1);
+            assert_eq!(there_and_back[frame_count / 2], expected[frame_count / 2]);
+
+            let back_and_there =
+                cyclic_fade_factors(FadeStyle::FromDarkAndBack, Easing::Linear, frame_count);
+            let inverted: Vec<f32> = expected.iter().map(|f| 1.0 - f).collect();
+            assert_close(&back_and_there, &inverted);
+
+            // Neither the turnaround nor the wrap back to the start repeats a frame.
+            if frame_count > 1 {
+                for i in 0..frame_count {
+                    let next = (i + 1) % frame_count;
+                    assert_ne!(
+                        there_and_back[i], there_and_back[next],
+                        "{there_and_back:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cycles_of_one_way_styles_are_plain_fades() {
+        for frame_count in 1..=6 {
+            for style in [FadeStyle::ToDark, FadeStyle::FromDark] {
+                assert_eq!(
+                    cyclic_fade_factors(style, Easing::Linear, frame_count),
+                    factors(style, frame_count)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn factors_stay_in_range_for_every_easing() {
+        let easings = [
+            Easing::Linear,
+            Easing::EaseInOut,
+            Easing::Exponential,
+            Easing::CubicBezier(0.3, -0.5, 0.7, 1.5),
+        ];
+        for style in STYLES {
+            for easing in easings {
+                for frame_count in [0, 1, 2, 3, 11, 60] {
+                    let factors = fade_factors(style, easing, frame_count);
+                    assert_eq!(factors.len(), frame_count);
+                    assert!(
+                        factors.iter().all(|f| (0.0..=1.0).contains(f)),
+                        "{style:?} {easing} {factors:?}"
+                    );
+                    if frame_count >= 3 {
+                        let start = match style {
+                            FadeStyle::ToDark | FadeStyle::ToDarkAndBack => 1.0,
+                            _ => 0.0,
+                        };
+                        let end = if style.turns_around() {
+                            start
+                        } else {
+                            1.0 - start
+                        };
+                        assert_eq!(factors[0], start, "{style:?} {easing}");
+                        assert_eq!(factors[frame_count - 1], end, "{style:?} {easing}");
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use crate::{Easing, FadeStyle, FaderError, Result, cyclic_fade_factors, fade_factors, style};
+
+/// Validated length of a fade and the static holds around it, in frames.
+///
+/// Every fade spans at least one frame, so a zero-length fade yields a single
+/// still frame showing where the fade starts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FadeTimeline {
+    framerate: u32,
+    fade_frames: usize,
+    hold_start: usize,
+    hold_middle: usize,
+    hold_end: usize,
+    looping: bool,
+    repeat: Repeat,
+}
+
+/// How often a timeline plays back to back in the output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Repeat {
+    /// Play the timeline this many times.
+    Times(u32),
+    /// Play the timeline as many whole times as come closest to this many
+    /// seconds, and at least once.
+    Duration(f32),
+}
+
+impl Default for Repeat {
+    fn default() -> Self {
+        Repeat::Times(1)
+    }
+}
+
+impl FadeTimeline {
+    /// Timeline of a fade lasting `duration` seconds at `framerate` frames
+    /// per second, without holds.
+    pub fn new(duration: f32, framerate: u32) -> Result<Self> {
+        let framerate = check_framerate(framerate)?;
+        let duration = check_seconds("duration", duration)?;
+        Ok(Self {
+            framerate,
+            fade_frames: frames("fade", (duration * framerate as f32).ceil())?.max(1),
+            hold_start: 0,
+            hold_middle: 0,
+            hold_end: 0,
+            looping: false,
+            repeat: Repeat::default(),
+        })
+    }
+
+    /// Keep the first frame on screen for `start` seconds before the fade,
+    /// the turnaround frame of there-and-back styles for `middle` seconds,
+    /// and the last frame for `end` seconds after it.
+    pub fn holds(self, start: f32, middle: f32, end: f32) -> Result<Self> {
+        let hold = |what, seconds| -> Result<usize> {
+            let seconds = check_seconds(what, seconds)?;
+            frames(what, (seconds * self.framerate as f32).round())
+        };
+        Self {
+            hold_start: hold("start hold", start)?,
+            hold_middle: hold("middle hold", middle)?,
+            hold_end: hold("end hold", end)?,
+            ..self
+        }
+        .checked()
+    }
+
+    /// Make the timeline a seamless cycle: there-and-back styles skip the
+    /// frame that would repeat the first one, and their end hold joins the
+    /// start hold on the first frame, where the cycle wraps around. One-way
+    /// styles still hold their last frame.
+    pub fn looping(self, looping: bool) -> Self {
+        Self { looping, ..self }
+    }
+
+    /// Play the timeline several times in a row.
+    pub fn repeat(self, repeat: Repeat) -> Result<Self> {
+        match repeat {
+            Repeat::Times(0) => {
+                return Err(FaderError::InvalidArgument(
+                    "the timeline must play at least once".into(),
+                ));
+            }
+            Repeat::Times(_) => {}
+            Repeat::Duration(seconds) => {
+                check_seconds("repeat duration", seconds)?;
+            }
+        }
+        Self { repeat, ..self }.checked()
+    }
+
+    /// Reject timelines spanning more than [`MAX_FRAMES`] for any style.
+    fn checked(self) -> Result<Self> {
+        for style in [FadeStyle::ToDark, FadeStyle::ToDarkAndBack] {
+            let total = self
+                .cycle_frame_count(style)
+                .checked_mul(self.repetitions(style));
+            check_frame_total("timeline", total)?;
+        }
+        Ok(self)
+    }
+
+    pub fn framerate(&self) -> u32 {
+        self.framerate
+    }
+
+    /// Number of frames the fade itself spans, always at least one.
+    pub fn fade_frame_count(&self) -> usize {
+        self.fade_frames
+    }
+
+    /// Number of frames of the whole timeline, holds and repeats included,
+    /// for a fade in `style`.
+    pub fn frame_count(&self, style: FadeStyle) -> usize {
+        self.cycle_frame_count(style) * self.repetitions(style)
+    }
+
+    /// Number of frames of a single play of the timeline.
+    fn cycle_frame_count(&self, style: FadeStyle) -> usize {
+        let middle = if style.turns_around() {
+            self.hold_middle
+        } else {
+            0
+        };
+        self.hold_start + self.fade_frames + middle + self.hold_end
+    }
+
+    fn repetitions(&self, style: FadeStyle) -> usize {
+        match self.repeat {
+            Repeat::Times(times) => times as usize,
+            Repeat::Duration(seconds) => {
+                let frames = seconds * self.framerate as f32;
+                ((frames / self.cycle_frame_count(style) as f32).round() as usize).max(1)
+            }
+        }
+    }
+
+    /// Brightness factor of each frame for `style` shaped by `easing`.
+    ///
+    /// The middle hold only applies to styles that
+    /// [turn around](FadeStyle::turns_around).
+    pub fn fade_factors(&self, style: FadeStyle, easing: Easing) -> Vec<f32> {
+        let fade = if self.looping {
+            cyclic_fade_factors(style, easing, self.fade_frames)
+        } else {
+            fade_factors(style, easing, self.fade_frames)
+        };
+        let mut factors = Vec::with_capacity(self.cycle_frame_count(style));
+        factors.extend(std::iter::repeat_n(fade[0], self.hold_start));
+        if style.turns_around() {
+            let (there, back) = fade.split_at(style::turnaround(fade.len(), self.looping));
+            factors.extend_from_slice(there);
+            factors.extend(std::iter::repeat_n(
+                there[there.len() - 1],
+                self.hold_middle,
+            ));
+            factors.extend_from_slice(back);
+        } else {
+            factors.extend_from_slice(&fade);
+        }
+        // A cycle that turns around wraps back to its first frame, so hold that.
+        let end = if self.looping && style.turns_around() {
+            fade[0]
+        } else {
+            fade[fade.len() - 1]
+        };
+        factors.extend(std::iter::repeat_n(end, self.hold_end));
+        factors.repeat(self.repetitions(style))
+    }
+}
+
+/// Most frames an animation may span, over 46 hours at 60 fps. The fade
+/// factors of every frame are held in memory, so longer ones are mistakes.
+pub(crate) const MAX_FRAMES: usize = 10_000_000;
+
+/// Convert a whole number of frames computed in floating point, naming the
+/// setting as `what`.
+pub(crate) fn frames(what: &str, frames: f32) -> Result<usize> {
+    // Checked in floating point, where huge counts cannot overflow.
+    if frames > MAX_FRAMES as f32 {
+        return Err(too_long(what));
+    }
+    Ok(frames as usize)
+}
+
+/// Check a frame count summed or multiplied with checked arithmetic, naming
+/// the setting as `what`.
+pub(crate) fn check_frame_total(what: &str, total: Option<usize>) -> Result<usize> {
+    total
+        .filter(|&total| total <= MAX_FRAMES)
+        .ok_or_else(|| too_long(what))
+}
+
+fn too_long(what: &str) -> FaderError {
+    FaderError::InvalidArgument(format!(
+        "{what} must not span more than {MAX_FRAMES} frames"
+    ))
+}
+
+/// Reject frame rates that cannot produce any frames.
+pub(crate) fn check_framerate(framerate: u32) -> Result<u32> {
+    if framerate == 0 {
+        return Err(FaderError::InvalidArgument(
+            "frame rate must be at least 1".into(),
+        ));
+    }
+    Ok(framerate)
+}
+
+/// Reject negative, infinite and NaN lengths of time, naming the setting as `what`.
+pub(crate) fn check_seconds(what: &str, seconds: f32) -> Result<f32> {
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(FaderError::InvalidArgument(format!(
+            "{what} must be a non-negative number of seconds, got {seconds}"
+        )));
+    }
+    Ok(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STYLES: [FadeStyle; 4] = [
+        FadeStyle::ToDark,
+        FadeStyle::FromDark,
+        FadeStyle::ToDarkAndBack,
+        FadeStyle::FromDarkAndBack,
+    ];
+
+    /// Timeline of a fade spanning exactly `frames` frames at 10 fps.
+    fn timeline(frames: usize) -> FadeTimeline {
+        FadeTimeline::new(frames as f32 / 10.0, 10).unwrap()
+    }
+
+    #[test]
+    fn zero_duration_is_a_single_frame() {
+        let timeline = FadeTimeline::new(0.0, 10).unwrap();
+        assert_eq!(timeline.fade_frame_count(), 1);
+        assert_eq!(
+            timeline.fade_factors(FadeStyle::ToDark, Easing::Linear),
+            [1.0]
+        );
+        assert_eq!(
+            timeline.fade_factors(FadeStyle::FromDark, Easing::Linear),
+            [0.0]
+        );
+        assert_eq!(
+            timeline.fade_factors(FadeStyle::ToDarkAndBack, Easing::Linear),
+            [1.0]
+        );
+        assert_eq!(
+            timeline.fade_factors(FadeStyle::FromDarkAndBack, Easing::Linear),
+            [0.0]
+        );
+    }
+
+    #[test]
+    fn frame_counts_match_the_factors() {
+        for frames in 1..=3 {
+            let timeline = timeline(frames);
+            assert_eq!(timeline.fade_frame_count(), frames);
+            for style in STYLES {
+                let factors = timeline.fade_factors(style, Easing::Linear);
+                assert_eq!(factors.len(), frames, "{style:?}");
+                assert_eq!(timeline.frame_count(style), frames, "{style:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn short_fades_reach_both_ends() {
+        assert_eq!(
+            timeline(2).fade_factors(FadeStyle::ToDark, Easing::Linear),
+            [1.0, 0.0]
+        );
+        assert_eq!(
+            timeline(3).fade_factors(FadeStyle::FromDarkAndBack, Easing::Linear),
+            [0.0, 1.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn looping_end_holds() {
+        let timeline = timeline(3).holds(0.0, 0.0, 0.2).unwrap().looping(true);
+        assert_eq!(
+            timeline.fade_factors(FadeStyle::ToDark, Easing::Linear),
+            [1.0, 0.5, 0.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            timeline.fade_factors(FadeStyle::FromDark, Easing::Linear),
+            [0.0, 0.5, 1.0, 1.0, 1.0]
+        );
+        assert_eq!(
+            timeline.fade_factors(FadeStyle::ToDarkAndBack, Easing::Linear),
+            [1.0, 0.0, 0.5, 1.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_lengths() {
+        assert!(FadeTimeline::new(-1.0, 10).is_err());
+        assert!(FadeTimeline::new(f32::NAN, 10).is_err());
+        assert!(FadeTimeline::new(f32::INFINITY, 10).is_err());
+        assert!(FadeTimeline::new(1.0, 0).is_err());
+        assert!(timeline(2).holds(-0.1, 0.0, 0.0).is_err());
+        assert!(timeline(2).repeat(Repeat::Times(0)).is_err());
+    }
+
+    #[test]
+    fn rejects_timelines_with_too_many_frames() {
+        assert!(FadeTimeline::new(1e30, 1000).is_err());
+        assert!(FadeTimeline::new(1e9, 30).is_err());
+        assert!(timeline(2).holds(0.0, 1e30, 0.0).is_err());
+        assert!(timeline(2).repeat(Repeat::Times(u32::MAX)).is_err());
+        assert!(timeline(2).repeat(Repeat::Duration(1e30)).is_err());
+        assert!(
+            FadeTimeline::new(2.0, 10)
+                .unwrap()
+                .looping(true)
+                .repeat(Repeat::Duration(1e30))
+                .is_err()
+        );
+        // Holds added after repeats count every repetition.
+        let repeated = timeline(1000).repeat(Repeat::Times(5000)).unwrap();
+        assert!(repeated.holds(0.0, 0.0, 1000.0).is_err());
+
+        let longest = FadeTimeline::new(MAX_FRAMES as f32 / 10.0, 10).unwrap();
+        assert_eq!(longest.fade_frame_count(), MAX_FRAMES);
+    }
+}
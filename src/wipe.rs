@@ -0,0 +1,182 @@
+use clap::ValueEnum;
+use std::f32::consts::TAU;
+
+use crate::{FaderError, Result, matte::Matte};
+
+/// Path the edge of a [`Wipe`] takes across the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum WipeShape {
+    /// A straight edge sweeping across the frame in the direction of the angle.
+    #[default]
+    Linear,
+    /// An iris closing in on the center, or opening from it when reversed.
+    Radial,
+    /// A clock hand sweeping clockwise around the center, starting at the angle.
+    Clock,
+}
+
+/// A fade that sweeps across the frame instead of changing every pixel at
+/// once.
+///
+/// The fade style still decides the direction: `to-dark` moves the edge
+/// forward over the image and `from-dark` plays the same wipe in reverse,
+/// uncovering it.
+///
+/// ```
+/// use fader::{Wipe, WipeShape};
+///
+/// // A diagonal wipe from the top left to the bottom right, with a soft edge.
+/// let wipe = Wipe::new(WipeShape::Linear).angle(45.0)?.softness(0.2);
+/// # Ok::<(), fader::FaderError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wipe {
+    shape: WipeShape,
+    angle: f32,
+    center: (f32, f32),
+    softness: f32,
+}
+
+impl Wipe {
+    /// A wipe of `shape` with a hard edge, at an angle of 0° around the center
+    /// of the frame.
+    pub fn new(shape: WipeShape) -> Self {
+        Self {
+            shape,
+            angle: 0.0,
+            center: (0.5, 0.5),
+            softness: 0.0,
+        }
+    }
+
+    /// Angle in degrees, clockwise. For linear wipes, the direction the edge
+    /// moves in, `0` being left to right and `90` top to bottom. For clock
+    /// wipes, where the hand starts, `0` being 12 o'clock.
+    pub fn angle(mut self, degrees: f32) -> Result<Self> {
+        self.angle = check_finite("wipe angle", degrees)?;
+        Ok(self)
+    }
+
+    /// Center of radial and clock wipes, as fractions of the frame width and
+    /// height from its top left corner. Defaults to the middle of the frame.
+    pub fn center(mut self, x: f32, y: f32) -> Result<Self> {
+        self.center = (
+            check_finite("wipe center", x)?,
+            check_finite("wipe center", y)?,
+        );
+        Ok(self)
+    }
+
+    /// Width of the blended band along the edge, as a fraction of the whole
+    /// way the edge travels, from `0.0` for a hard edge to `1.0`.
+    pub fn softness(mut self, softness: f32) -> Self {
+        self.softness = softness.clamp(0.0, 1.0);
+        self
+    }
+
+    /// The order pixels of a `width` by `height` frame are faded in.
+    pub(crate) fn matte(&self, width: u32, height: u32) -> Matte {
+        let (w, h) = (width as f32, height as f32);
+        let (cx, cy) = (self.center.0 * w, self.center.1 * h);
+        let corners = [(0.0, 0.0), (w, 0.0), (0.0, h), (w, h)];
+        let angle = self.angle.to_radians();
+
+        let position: Box<dyn Fn(f32, f32) -> f32> = match self.shape {
+            WipeShape::Linear => {
+                let (dx, dy) = (angle.cos(), angle.sin());
+                let project = move |(x, y): (f32, f32)| x * dx + y * dy;
+                let start = corners.map(project).into_iter().fold(f32::MAX, f32::min);
+                let end = corners.map(project).into_iter().fold(f32::MIN, f32::max);
+                let length = (end - start).max(f32::EPSILON);
+                Box::new(move |x, y| (project((x, y)) - start) / length)
+            }
+            WipeShape::Radial => {
+                let radius = corners
+                    .map(|(x, y)| (x - cx).hypot(y - cy))
+                    .into_iter()
+                    .fold(f32::EPSILON, f32::max);
+                // The farthest pixels are covered first, closing the iris.
+                Box::new(move |x, y| 1.0 - (x - cx).hypot(y - cy) / radius)
+            }
+            WipeShape::Clock => Box::new(move |x, y| {
+                // Measured clockwise from 12 o'clock, with y pointing down.
+                let hand = (x - cx).atan2(cy - y);
+                (hand - angle).rem_euclid(TAU) / TAU
+            }),
+        };
+
+        let positions = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x as f32 + 0.5, y as f32 + 0.5)))
+            .map(|(x, y)| position(x, y))
+            .collect();
+        Matte::new(width, height, positions, self.softness)
+    }
+}
+
+/// Reject infinite and NaN settings, naming the setting as `what`.
+fn check_finite(what: &str, value: f32) -> Result<f32> {
+    if !value.is_finite() {
+        return Err(FaderError::InvalidArgument(format!(
+            "{what} must be a finite number, got {value}"
+        )));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Weights of the pixels halfway through a hard wipe, `0.0` where faded.
+    fn halfway(wipe: Wipe, width: u32, height: u32) -> Vec<f32> {
+        wipe.matte(width, height).weights(0.5).collect()
+    }
+
+    #[test]
+    fn linear_wipes_move_in_the_direction_of_the_angle() {
+        let linear = Wipe::new(WipeShape::Linear);
+        assert_eq!(halfway(linear, 4, 1), [0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(
+            halfway(linear.angle(180.0).unwrap(), 4, 1),
+            [1.0, 1.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            halfway(linear.angle(90.0).unwrap(), 1, 4),
+            [0.0, 0.0, 1.0, 1.0]
+        );
+        assert_eq!(
+            halfway(linear.angle(-90.0).unwrap(), 1, 4),
+            [1.0, 1.0, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn radial_wipes_close_on_the_center() {
+        let radial = Wipe::new(WipeShape::Radial);
+        // The corners of a 3×3 frame go first, then the edges.
+        let expected = [0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 0.0, 1.0, 0.0];
+        assert_eq!(halfway(radial, 3, 3), expected);
+        let corner = radial.center(0.0, 0.0).unwrap();
+        assert_eq!(halfway(corner, 4, 1), [1.0, 1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn clock_wipes_sweep_clockwise_from_the_angle() {
+        let clock = Wipe::new(WipeShape::Clock);
+        assert_eq!(halfway(clock, 2, 2), [1.0, 0.0, 1.0, 0.0]);
+        assert_eq!(
+            halfway(clock.angle(90.0).unwrap(), 2, 2),
+            [1.0, 1.0, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn rejects_non_finite_settings() {
+        let wipe = Wipe::new(WipeShape::Clock);
+        assert!(wipe.angle(f32::NAN).is_err());
+        assert!(wipe.angle(f32::INFINITY).is_err());
+        assert!(wipe.center(0.5, f32::NEG_INFINITY).is_err());
+        assert!(wipe.center(f32::NAN, 0.5).is_err());
+        assert!(wipe.angle(-720.0).is_ok() && wipe.center(-1.0, 2.0).is_ok());
+    }
+}